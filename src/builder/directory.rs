@@ -1,9 +1,9 @@
+use super::{read_dir_sorted, MetadataBuilder};
 use crate::{
     error::PortableAudioLibraryResult,
-    serialization::{AudioMetadata, Metadata, Playlist},
+    serialization::{AudioMetadata, Metadata},
 };
 use std::{
-    collections::HashMap,
     fs::DirEntry,
     path::{Path, PathBuf},
 };
@@ -11,39 +11,33 @@ use std::{
 const ROOT: &str = "root";
 
 /// Builds a [`Metadata`] from a directory.
+///
+/// Audio files at the top level belong to the `root` playlist, and every subdirectory is a
+/// playlist of the audio files it contains. Tracks are ordered by file name.
 pub fn build_metadata_from_directory(
     path: impl Into<PathBuf>,
 ) -> PortableAudioLibraryResult<Metadata> {
     let path = path.into();
 
-    let mut metadata = Metadata::default();
-    let mut hashmap = HashMap::new();
-
-    for entry in std::fs::read_dir(path)? {
-        let entry = entry?;
+    let mut metadata = MetadataBuilder::default();
 
+    for entry in read_dir_sorted(&path)? {
         match entry.file_type()? {
             file_type if file_type.is_dir() => {
                 let playlist_name = entry.file_name().to_str().unwrap().to_string();
 
-                for entry in std::fs::read_dir(entry.path())? {
-                    let entry = entry?;
-
-                    insert_audio_metadata_to_hashmap(entry, &playlist_name, &mut hashmap)?;
+                for entry in read_dir_sorted(&entry.path())? {
+                    insert_audio_metadata(entry, &playlist_name, &mut metadata)?;
                 }
             }
             file_type if file_type.is_file() => {
-                insert_audio_metadata_to_hashmap(entry, &ROOT.to_string(), &mut hashmap)?;
+                insert_audio_metadata(entry, ROOT, &mut metadata)?;
             }
             _ => continue,
         }
     }
 
-    hashmap.into_iter().for_each(|(_, hashmap_metadata)| {
-        metadata.audios.push(hashmap_metadata);
-    });
-
-    Ok(metadata)
+    Ok(metadata.build())
 }
 
 /// Builds a directory from a [`Metadata`].
@@ -54,8 +48,16 @@ pub fn build_directory_from_metadata(
     let path = path.into();
     std::fs::create_dir_all(&path)?;
 
-    for audio_metadata in &metadata.audios {
-        write_audio_to_path(&path, audio_metadata)?;
+    for (playlist, audios) in metadata.playlist_audios()? {
+        let playlist_path = match playlist.name == ROOT {
+            true => path.clone(),
+            false => path.join(&playlist.name),
+        };
+        std::fs::create_dir_all(&playlist_path)?;
+
+        for audio_metadata in audios {
+            write_audio_to_path(&playlist_path, audio_metadata)?;
+        }
     }
 
     Ok(())
@@ -65,60 +67,21 @@ fn write_audio_to_path(
     path: &Path,
     audio_metadata: &AudioMetadata,
 ) -> PortableAudioLibraryResult<()> {
-    for playlist in &audio_metadata.playlists {
-        match playlist == ROOT {
-            true => {
-                std::fs::copy(&audio_metadata.path, path.join(&audio_metadata.name))?;
-            }
-            false => {
-                std::fs::create_dir_all(path.join(playlist))?;
-                std::fs::copy(
-                    &audio_metadata.path,
-                    path.join(playlist).join(&audio_metadata.name),
-                )?;
-            }
-        }
-    }
+    std::fs::copy(&audio_metadata.path, path.join(&audio_metadata.name))?;
 
     Ok(())
 }
 
-fn create_audio_metadata_from_entry(
-    entry: DirEntry,
-    playlist: &Playlist,
-) -> PortableAudioLibraryResult<AudioMetadata> {
-    let path = entry.path();
-    let name = path.file_name().unwrap().to_str().unwrap().to_string();
-    let size = path.metadata()?.len();
-
-    Ok(AudioMetadata {
-        name,
-        size,
-        playlists: vec![playlist.to_string()],
-        path,
-    })
-}
-
-fn insert_audio_metadata_to_hashmap(
+fn insert_audio_metadata(
     entry: DirEntry,
-    playlist_name: &Playlist,
-    hashmap: &mut HashMap<String, AudioMetadata>,
+    playlist_name: &str,
+    metadata: &mut MetadataBuilder,
 ) -> PortableAudioLibraryResult<()> {
     if entry.file_type()?.is_file() {
-        let audio_metadata = create_audio_metadata_from_entry(entry, playlist_name)?;
+        let path = entry.path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
 
-        match hashmap.contains_key(&audio_metadata.name) {
-            true => {
-                let audio_metadata: &mut AudioMetadata =
-                    hashmap.get_mut(&audio_metadata.name).unwrap();
-                audio_metadata.playlists.push(playlist_name.clone());
-            }
-            false => {
-                hashmap
-                    .entry(audio_metadata.name.clone())
-                    .or_insert_with(|| audio_metadata);
-            }
-        }
+        metadata.add_track(playlist_name, name, path)?;
     }
 
     Ok(())
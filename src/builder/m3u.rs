@@ -1,63 +1,80 @@
+use super::{read_dir_sorted, resolve_entry, MetadataBuilder};
 use crate::{
-    error::PortableAudioLibraryResult,
-    serialization::{AudioMetadata, Metadata},
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    serialization::Metadata,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const HEADER: &str = "#EXTM3U";
+const EXTINF: &str = "#EXTINF:";
+const BOM: char = '\u{feff}';
+
+/// Entry of a .m3u file.
+struct Entry {
+    path: String,
+    title: Option<String>,
+    duration: Option<Duration>,
+}
 
-/// Builds a metadata object from a .m3u file.
+/// Builds a metadata object from a directory of .m3u and .m3u8 files.
+///
+/// Each file is a playlist named after the file. Both plain and extended M3U are supported,
+/// `#EXTINF` lines provide the title and the duration of the next entry. Entries can be paths
+/// or `file://` URIs, relative paths are resolved against the directory of the playlist and
+/// remote URLs are skipped.
 pub fn build_metadata_from_m3u(path: impl Into<PathBuf>) -> PortableAudioLibraryResult<Metadata> {
     let path = path.into();
 
-    let mut metadata = Metadata::default();
-    let mut hashmap = HashMap::new();
+    let mut metadata = MetadataBuilder::default();
 
-    for entry in std::fs::read_dir(path)? {
-        let entry = entry?;
-        let playlist_name = entry.file_name().to_string_lossy().to_string();
+    for entry in read_dir_sorted(&path)? {
+        let entry_path = entry.path();
 
-        if entry
-            .path()
+        if entry_path
             .extension()
-            .map(|ext| ext == "m3u")
-            .unwrap_or(false)
+            .is_none_or(|ext| !ext.eq_ignore_ascii_case("m3u") && !ext.eq_ignore_ascii_case("m3u8"))
         {
-            let content = std::fs::read_to_string(entry.path())?;
-            let audios: Vec<&str> = content.split('\n').collect();
-
-            for audio in audios {
-                let path = PathBuf::from(audio);
-                let name = path.file_name().unwrap().to_string_lossy().to_string();
-                let size = path.metadata()?.len();
-
-                match hashmap.contains_key(&name) {
-                    true => {
-                        let audio: &mut AudioMetadata = hashmap.get_mut(&name).unwrap();
-                        audio.playlists.push(playlist_name.clone());
-                    }
-                    false => {
-                        hashmap.insert(
-                            name.clone(),
-                            AudioMetadata {
-                                path,
-                                name,
-                                size,
-                                playlists: vec![playlist_name.clone()],
-                            },
-                        );
-                    }
-                }
-            }
+            continue;
         }
-    }
 
-    for (_, audio) in hashmap {
-        metadata.audios.push(audio);
+        let playlist_name = entry_path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let playlist_directory = entry_path.parent().unwrap_or(Path::new(""));
+        let content = decode_m3u(&entry_path, std::fs::read(&entry_path)?)?;
+
+        for m3u_entry in parse_m3u(&content) {
+            let Some(audio_path) = resolve_entry(&m3u_entry.path, playlist_directory) else {
+                continue;
+            };
+
+            let name = audio_path
+                .file_name()
+                .ok_or_else(|| {
+                    PortableAudioLibraryError::InvalidPlaylist(entry_path.display().to_string())
+                })?
+                .to_string_lossy()
+                .to_string();
+
+            let audio = metadata.add_track(&playlist_name, name, audio_path)?;
+            audio.title = audio.title.take().or(m3u_entry.title);
+            audio.duration = audio.duration.or(m3u_entry.duration);
+        }
     }
 
-    Ok(metadata)
+    Ok(metadata.build())
 }
 
-/// Builds a .m3u file from a metadata object.
+/// Builds a directory of extended .m3u files from a metadata object.
+///
+/// The audios are copied into the directory, and each playlist refers to them by file name so
+/// the directory can be moved around.
 pub fn build_m3u_from_metadata(
     path: impl Into<PathBuf>,
     metadata: &Metadata,
@@ -65,31 +82,186 @@ pub fn build_m3u_from_metadata(
     let path: PathBuf = path.into();
     std::fs::create_dir_all(&path)?;
 
-    let mut hashmap = HashMap::new();
-
     for audio in &metadata.audios {
-        let audio_path = path.join(&audio.name);
-        std::fs::copy(&audio.path, audio_path.clone())?;
-
-        for playlist in &audio.playlists {
-            let playlist_path = path.join(playlist).with_extension("m3u");
-
-            match hashmap.contains_key(&playlist_path) {
-                true => {
-                    let content: &mut String = hashmap.get_mut(&playlist_path).unwrap();
-                    content.push('\n');
-                    content.push_str(audio_path.to_string_lossy().as_ref());
-                }
-                false => {
-                    hashmap.insert(playlist_path, audio_path.to_string_lossy().to_string());
-                }
-            }
-        }
+        std::fs::copy(&audio.path, path.join(&audio.name))?;
     }
 
-    for (playlist_path, content) in hashmap {
-        std::fs::write(playlist_path, content)?;
+    for (playlist, audios) in metadata.playlist_audios()? {
+        let mut content = format!("{}\n", HEADER);
+
+        for audio in audios {
+            let duration = audio
+                .duration
+                .map_or(-1, |duration| duration.as_secs() as i64);
+            let title = audio.title.clone().unwrap_or_else(|| {
+                PathBuf::from(&audio.name)
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
+
+            writeln!(content, "{}{},{}", EXTINF, duration, title).unwrap();
+            writeln!(content, "{}", audio.name).unwrap();
+        }
+
+        std::fs::write(path.join(format!("{}.m3u", playlist.name)), content)?;
     }
 
     Ok(())
 }
+
+/// Decodes the content of a playlist file.
+///
+/// .m3u8 files are always UTF-8, while legacy .m3u files that aren't valid UTF-8 are read as
+/// Latin-1, the encoding they were originally defined with.
+fn decode_m3u(path: &Path, content: Vec<u8>) -> PortableAudioLibraryResult<String> {
+    match String::from_utf8(content) {
+        Ok(content) => Ok(content),
+        Err(error)
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u")) =>
+        {
+            Ok(error
+                .as_bytes()
+                .iter()
+                .map(|&byte| char::from(byte))
+                .collect())
+        }
+        Err(_) => Err(PortableAudioLibraryError::InvalidPlaylist(
+            path.display().to_string(),
+        )),
+    }
+}
+
+/// Parses the entries of a plain or extended .m3u file.
+///
+/// Blank lines, comments and unknown directives are skipped.
+fn parse_m3u(content: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut extinf = None;
+
+    for line in content.trim_start_matches(BOM).lines() {
+        let line = line.trim();
+
+        if let Some(info) = line.strip_prefix(EXTINF) {
+            extinf = Some(parse_extinf(info));
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (duration, title) = extinf.take().unwrap_or_default();
+        entries.push(Entry {
+            path: line.to_string(),
+            title,
+            duration,
+        });
+    }
+
+    entries
+}
+
+/// Parses the duration and the title of an `#EXTINF:<duration> [attributes],<title>` line.
+fn parse_extinf(info: &str) -> (Option<Duration>, Option<String>) {
+    // Attribute values may contain commas, so the title starts after the first unquoted one.
+    let mut quoted = false;
+    let (duration, title) = info
+        .char_indices()
+        .find(|&(_, c)| {
+            quoted ^= c == '"';
+            c == ',' && !quoted
+        })
+        .map_or((info, ""), |(index, _)| {
+            (&info[..index], &info[index + 1..])
+        });
+
+    // Streams have a duration of -1, which like any out of range value is dropped.
+    let duration = duration
+        .split_whitespace()
+        .next()
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .and_then(|duration| Duration::try_from_secs_f64(duration).ok());
+    let title = Some(title.trim())
+        .filter(|title| !title.is_empty())
+        .map(str::to_string);
+
+    (duration, title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_extinf() {
+        assert_eq!(
+            parse_extinf("215,Artist - Title"),
+            (
+                Some(Duration::from_secs(215)),
+                Some("Artist - Title".to_string())
+            )
+        );
+        assert_eq!(
+            parse_extinf("1.5,Title, with comma"),
+            (
+                Some(Duration::from_millis(1500)),
+                Some("Title, with comma".to_string())
+            )
+        );
+        assert_eq!(parse_extinf("-1,"), (None, None));
+        assert_eq!(parse_extinf("abc"), (None, None));
+        assert_eq!(
+            parse_extinf("1e30,Title"),
+            (None, Some("Title".to_string()))
+        );
+        assert_eq!(parse_extinf("NaN,"), (None, None));
+    }
+
+    #[test]
+    fn parses_extinf_attributes() {
+        assert_eq!(
+            parse_extinf(r#"10 tvg-name="a,b" group-title="c",Title"#),
+            (Some(Duration::from_secs(10)), Some("Title".to_string()))
+        );
+    }
+
+    #[test]
+    fn decodes_legacy_m3u_as_latin1() {
+        assert_eq!(
+            decode_m3u(Path::new("a.m3u"), b"caf\xe9.mp3".to_vec()).unwrap(),
+            "caf\u{e9}.mp3"
+        );
+        assert_eq!(
+            decode_m3u(Path::new("a.m3u"), "caf\u{e9}.mp3".as_bytes().to_vec()).unwrap(),
+            "caf\u{e9}.mp3"
+        );
+        assert!(matches!(
+            decode_m3u(Path::new("a.m3u8"), b"caf\xe9.mp3".to_vec()),
+            Err(PortableAudioLibraryError::InvalidPlaylist(_))
+        ));
+    }
+
+    #[test]
+    fn parses_extended_m3u() {
+        let entries = parse_m3u(
+            "\u{feff}#EXTM3U\r\n\
+             \r\n\
+             # comment\r\n\
+             #EXTINF:10,A\r\n\
+             #EXTGRP:group\r\n\
+             a.mp3\r\n\
+             b.mp3\r\n",
+        );
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "a.mp3");
+        assert_eq!(entries[0].title.as_deref(), Some("A"));
+        assert_eq!(entries[0].duration, Some(Duration::from_secs(10)));
+        assert_eq!(entries[1].path, "b.mp3");
+        assert_eq!(entries[1].title, None);
+    }
+}
@@ -1,2 +1,164 @@
+use crate::{
+    error::PortableAudioLibraryResult,
+    serialization::{AudioMetadata, Metadata, Playlist},
+};
+use percent_encoding::percent_decode_str;
+use std::{
+    collections::HashMap,
+    fs::DirEntry,
+    path::{Path, PathBuf},
+};
+
 pub mod directory;
 pub mod m3u;
+pub mod pls;
+pub mod xspf;
+
+const FILE_SCHEME: &str = "file://";
+
+/// Metadata under construction, with its audios and playlists looked up by name.
+#[derive(Default)]
+struct MetadataBuilder {
+    metadata: Metadata,
+    audio_indices: HashMap<String, usize>,
+    playlist_indices: HashMap<String, usize>,
+}
+
+impl MetadataBuilder {
+    /// Appends the audio file to the end of the playlist, creating either if needed, and returns
+    /// the metadata of the audio.
+    fn add_track(
+        &mut self,
+        playlist_name: &str,
+        name: String,
+        path: PathBuf,
+    ) -> PortableAudioLibraryResult<&mut AudioMetadata> {
+        let audio_index = match self.audio_indices.get(&name) {
+            Some(&audio_index) => audio_index,
+            None => {
+                let size = path.metadata()?.len();
+
+                self.audio_indices
+                    .insert(name.clone(), self.metadata.audios.len());
+                self.metadata.audios.push(AudioMetadata {
+                    name: name.clone(),
+                    size,
+                    path,
+                    ..Default::default()
+                });
+                self.metadata.audios.len() - 1
+            }
+        };
+
+        let playlist_index = match self.playlist_indices.get(playlist_name) {
+            Some(&playlist_index) => playlist_index,
+            None => {
+                self.playlist_indices
+                    .insert(playlist_name.to_string(), self.metadata.playlists.len());
+                self.metadata.playlists.push(Playlist {
+                    name: playlist_name.to_string(),
+                    tracks: vec![],
+                });
+                self.metadata.playlists.len() - 1
+            }
+        };
+
+        self.metadata.playlists[playlist_index].tracks.push(name);
+
+        Ok(&mut self.metadata.audios[audio_index])
+    }
+
+    fn build(self) -> Metadata {
+        self.metadata
+    }
+}
+
+/// Returns the entries of the directory sorted by file name, since [`std::fs::read_dir`] returns
+/// them in an unspecified order.
+fn read_dir_sorted(path: &Path) -> PortableAudioLibraryResult<Vec<DirEntry>> {
+    let mut entries = std::fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    Ok(entries)
+}
+
+/// Resolves an entry to a local path, or `None` if it is a remote URL.
+fn resolve_entry(entry: &str, playlist_directory: &Path) -> Option<PathBuf> {
+    let path = match entry.strip_prefix(FILE_SCHEME) {
+        Some(path) => {
+            // Skip the host, which is usually empty or `localhost`.
+            let path = &path[path.find('/').unwrap_or(path.len())..];
+            let path = percent_decode_str(path).decode_utf8_lossy().to_string();
+
+            // `file:///C:/Music` refers to `C:/Music` on Windows.
+            match path.as_bytes() {
+                [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => {
+                    path[1..].to_string()
+                }
+                _ => path,
+            }
+        }
+        None if entry.contains("://") => return None,
+        None => entry.to_string(),
+    };
+
+    // Playlists written on Windows separate their paths with backslashes.
+    let path = match cfg!(windows) {
+        true => path,
+        false => path.replace('\\', "/"),
+    };
+
+    Some(playlist_directory.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn resolves_relative_entries_against_playlist() {
+        let directory = Path::new("/music");
+
+        assert_eq!(
+            resolve_entry("a.mp3", directory),
+            Some(PathBuf::from("/music/a.mp3"))
+        );
+        assert_eq!(
+            resolve_entry("/other/a.mp3", directory),
+            Some(PathBuf::from("/other/a.mp3"))
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn resolves_backslashes() {
+        assert_eq!(
+            resolve_entry(r"sub\a.mp3", Path::new("/music")),
+            Some(PathBuf::from("/music/sub/a.mp3"))
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn resolves_file_uris() {
+        let directory = Path::new("/music");
+
+        assert_eq!(
+            resolve_entry("file:///other/a%20b.mp3", directory),
+            Some(PathBuf::from("/other/a b.mp3"))
+        );
+        assert_eq!(
+            resolve_entry("file://localhost/other/a.mp3", directory),
+            Some(PathBuf::from("/other/a.mp3"))
+        );
+    }
+
+    #[test]
+    fn skips_remote_entries() {
+        assert_eq!(
+            resolve_entry("http://radio.example.com/stream", Path::new("/music")),
+            None
+        );
+    }
+}
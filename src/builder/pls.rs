@@ -0,0 +1,238 @@
+use super::{read_dir_sorted, resolve_entry, MetadataBuilder};
+use crate::{
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    serialization::{AudioMetadata, Metadata},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const SECTION: &str = "[playlist]";
+const VERSION: u32 = 2;
+const BOM: char = '\u{feff}';
+
+/// Entry of a .pls file.
+#[derive(Default)]
+struct Entry {
+    file: Option<String>,
+    title: Option<String>,
+    length: Option<Duration>,
+}
+
+/// Builds a metadata object from a directory of .pls files.
+///
+/// Each .pls file is a playlist named after the file. Entries can be paths or `file://` URIs,
+/// relative paths are resolved against the directory of the .pls file and remote streams are
+/// skipped.
+pub fn build_metadata_from_pls(path: impl Into<PathBuf>) -> PortableAudioLibraryResult<Metadata> {
+    let path = path.into();
+
+    let mut metadata = MetadataBuilder::default();
+
+    for entry in read_dir_sorted(&path)? {
+        let entry_path = entry.path();
+
+        if entry_path
+            .extension()
+            .is_none_or(|ext| !ext.eq_ignore_ascii_case("pls"))
+        {
+            continue;
+        }
+
+        let playlist_name = entry_path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let playlist_directory = entry_path.parent().unwrap_or(Path::new(""));
+        let content = std::fs::read_to_string(&entry_path)?;
+
+        for pls_entry in parse_pls(&content, &entry_path)? {
+            let Some(audio_path) = pls_entry
+                .file
+                .as_deref()
+                .and_then(|file| resolve_entry(file, playlist_directory))
+            else {
+                continue;
+            };
+
+            let name = audio_path
+                .file_name()
+                .ok_or_else(|| {
+                    PortableAudioLibraryError::InvalidPlaylist(entry_path.display().to_string())
+                })?
+                .to_string_lossy()
+                .to_string();
+
+            let audio = metadata.add_track(&playlist_name, name, audio_path)?;
+            audio.title = audio.title.take().or(pls_entry.title);
+            audio.duration = audio.duration.or(pls_entry.length);
+        }
+    }
+
+    Ok(metadata.build())
+}
+
+/// Builds a directory of .pls files from a metadata object.
+///
+/// The audios are copied into the directory, and each playlist refers to them by file name.
+pub fn build_pls_from_metadata(
+    path: impl Into<PathBuf>,
+    metadata: &Metadata,
+) -> PortableAudioLibraryResult<()> {
+    let path: PathBuf = path.into();
+    std::fs::create_dir_all(&path)?;
+
+    for audio in &metadata.audios {
+        std::fs::copy(&audio.path, path.join(&audio.name))?;
+    }
+
+    for (playlist, audios) in metadata.playlist_audios()? {
+        let playlist_path = path.join(format!("{}.pls", playlist.name));
+        std::fs::write(playlist_path, format_pls(&audios))?;
+    }
+
+    Ok(())
+}
+
+/// Parses the entries of a .pls file, ordered by their number.
+fn parse_pls(content: &str, path: &Path) -> PortableAudioLibraryResult<Vec<Entry>> {
+    let invalid_playlist =
+        || PortableAudioLibraryError::InvalidPlaylist(path.display().to_string());
+
+    let mut lines = content
+        .trim_start_matches(BOM)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'));
+
+    if !lines
+        .next()
+        .is_some_and(|line| line.eq_ignore_ascii_case(SECTION))
+    {
+        return Err(invalid_playlist());
+    }
+
+    let mut entries: BTreeMap<u32, Entry> = BTreeMap::new();
+    let mut number_of_entries = None;
+
+    for line in lines {
+        let (key, value) = line.split_once('=').ok_or_else(invalid_playlist)?;
+        let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+
+        if key == "numberofentries" {
+            number_of_entries = Some(value.parse::<u32>().map_err(|_| invalid_playlist())?);
+            continue;
+        }
+
+        let Some((field, number)) = ["file", "title", "length"]
+            .into_iter()
+            .find_map(|field| Some((field, key.strip_prefix(field)?.parse::<u32>().ok()?)))
+        else {
+            // Version and unknown keys.
+            continue;
+        };
+
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.file = Some(value.to_string()),
+            "title" => entry.title = Some(value.to_string()),
+            _ => {
+                // Streams have a length of -1.
+                entry.length = value.parse::<u64>().ok().map(Duration::from_secs);
+            }
+        }
+    }
+
+    if let Some(number_of_entries) = number_of_entries {
+        entries.retain(|&number, _| number <= number_of_entries);
+    }
+
+    Ok(entries.into_values().collect())
+}
+
+/// Formats the audios as a .pls file.
+fn format_pls(audios: &[&AudioMetadata]) -> String {
+    let mut content = format!("{}\n", SECTION);
+
+    for (number, audio) in (1..).zip(audios) {
+        writeln!(content, "File{}={}", number, audio.name).unwrap();
+
+        if let Some(title) = &audio.title {
+            writeln!(content, "Title{}={}", number, title).unwrap();
+        }
+
+        let length = audio
+            .duration
+            .map_or(-1, |duration| duration.as_secs() as i64);
+        writeln!(content, "Length{}={}", number, length).unwrap();
+    }
+
+    writeln!(content, "NumberOfEntries={}", audios.len()).unwrap();
+    writeln!(content, "Version={}", VERSION).unwrap();
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> PortableAudioLibraryResult<Vec<Entry>> {
+        parse_pls(content, Path::new("test.pls"))
+    }
+
+    #[test]
+    fn parses_entries_in_number_order() {
+        let entries = parse(
+            "[playlist]\r\n\
+             File2=b.mp3\r\n\
+             Title2=B\r\n\
+             Length2=-1\r\n\
+             File1=a.mp3\r\n\
+             Title1=A = 1\r\n\
+             Length1=215\r\n\
+             NumberOfEntries=2\r\n\
+             Version=2\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file.as_deref(), Some("a.mp3"));
+        assert_eq!(entries[0].title.as_deref(), Some("A = 1"));
+        assert_eq!(entries[0].length, Some(Duration::from_secs(215)));
+        assert_eq!(entries[1].file.as_deref(), Some("b.mp3"));
+        assert_eq!(entries[1].length, None);
+    }
+
+    #[test]
+    fn ignores_case_comments_and_bom() {
+        let entries = parse("\u{feff}[Playlist]\n; comment\n\nfile1=a.mp3\n").unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file.as_deref(), Some("a.mp3"));
+    }
+
+    #[test]
+    fn truncates_to_number_of_entries() {
+        let entries = parse("[playlist]\nFile1=a.mp3\nFile2=b.mp3\nNumberOfEntries=1\n").unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file.as_deref(), Some("a.mp3"));
+    }
+
+    #[test]
+    fn rejects_malformed_playlists() {
+        assert!(matches!(
+            parse("File1=a.mp3\n"),
+            Err(PortableAudioLibraryError::InvalidPlaylist(_))
+        ));
+        assert!(matches!(
+            parse("[playlist]\nFile1\n"),
+            Err(PortableAudioLibraryError::InvalidPlaylist(_))
+        ));
+    }
+}
@@ -0,0 +1,390 @@
+use super::{read_dir_sorted, resolve_entry, MetadataBuilder};
+use crate::{
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    serialization::{AudioMetadata, Metadata},
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::{
+    events::{BytesDecl, BytesText, Event},
+    Reader, Writer,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const NAMESPACE: &str = "http://xspf.org/ns/0/";
+/// Characters escaped in the locations of exported tracks.
+const LOCATION: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Track of a .xspf file.
+#[derive(Default)]
+struct Track {
+    location: Option<String>,
+    title: Option<String>,
+    creator: Option<String>,
+    album: Option<String>,
+    duration: Option<String>,
+    track_number: Option<String>,
+    image: Option<String>,
+}
+
+/// Builds a metadata object from a directory of .xspf files.
+///
+/// Each .xspf file is a playlist named after the file. Only tracks with a local location are
+/// imported, relative locations are resolved against the directory of the .xspf file.
+pub fn build_metadata_from_xspf(path: impl Into<PathBuf>) -> PortableAudioLibraryResult<Metadata> {
+    let path = path.into();
+
+    let mut metadata = MetadataBuilder::default();
+
+    for entry in read_dir_sorted(&path)? {
+        let entry_path = entry.path();
+
+        if entry_path
+            .extension()
+            .is_none_or(|ext| !ext.eq_ignore_ascii_case("xspf"))
+        {
+            continue;
+        }
+
+        let playlist_name = entry_path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let playlist_directory = entry_path.parent().unwrap_or(Path::new(""));
+        let content = std::fs::read_to_string(&entry_path)?;
+
+        for track in parse_xspf(&content, &entry_path)? {
+            let Some(audio_path) = track
+                .location
+                .as_deref()
+                .and_then(|location| resolve_location(location, playlist_directory))
+            else {
+                continue;
+            };
+
+            let name = audio_path
+                .file_name()
+                .ok_or_else(|| {
+                    PortableAudioLibraryError::InvalidPlaylist(entry_path.display().to_string())
+                })?
+                .to_string_lossy()
+                .to_string();
+
+            let audio = metadata.add_track(&playlist_name, name, audio_path)?;
+            audio.title = audio.title.take().or(track.title);
+            audio.creator = audio.creator.take().or(track.creator);
+            audio.album = audio.album.take().or(track.album);
+            audio.duration = audio.duration.or(track
+                .duration
+                .and_then(|duration| duration.parse().ok())
+                .map(Duration::from_millis));
+            audio.track_number = audio
+                .track_number
+                .or(track.track_number.and_then(|number| number.parse().ok()));
+            audio.image = audio.image.take().or(track.image);
+        }
+    }
+
+    Ok(metadata.build())
+}
+
+/// Builds a directory of .xspf files from a metadata object.
+///
+/// The audios are copied into the directory, and each playlist refers to them by a location
+/// relative to the .xspf file.
+pub fn build_xspf_from_metadata(
+    path: impl Into<PathBuf>,
+    metadata: &Metadata,
+) -> PortableAudioLibraryResult<()> {
+    let path: PathBuf = path.into();
+    std::fs::create_dir_all(&path)?;
+
+    for audio in &metadata.audios {
+        std::fs::copy(&audio.path, path.join(&audio.name))?;
+    }
+
+    for (playlist, audios) in metadata.playlist_audios()? {
+        let playlist_path = path.join(format!("{}.xspf", playlist.name));
+        std::fs::write(playlist_path, format_xspf(&playlist.name, &audios)?)?;
+    }
+
+    Ok(())
+}
+
+/// Parses the tracks of a .xspf file.
+fn parse_xspf(content: &str, path: &Path) -> PortableAudioLibraryResult<Vec<Track>> {
+    let invalid_playlist = |error: quick_xml::Error| {
+        PortableAudioLibraryError::InvalidPlaylist(format!("{}: {}", path.display(), error))
+    };
+
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut tracks = vec![];
+    // Local names of the open elements.
+    let mut elements: Vec<String> = vec![];
+    let mut has_playlist = false;
+
+    loop {
+        let text = match reader.read_event().map_err(invalid_playlist)? {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                has_playlist |= elements.is_empty() && name == "playlist";
+                if elements == ["playlist", "trackList"] && name == "track" {
+                    tracks.push(Track::default());
+                }
+
+                elements.push(name);
+                continue;
+            }
+            Event::End(_) => {
+                elements.pop();
+                continue;
+            }
+            Event::Text(text) => text.unescape().map_err(invalid_playlist)?.to_string(),
+            Event::CData(data) => String::from_utf8_lossy(&data).to_string(),
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let Some(track) = tracks.last_mut() else {
+            continue;
+        };
+        let [playlist, track_list, track_element, field] = elements.as_slice() else {
+            continue;
+        };
+        if [playlist, track_list, track_element] != ["playlist", "trackList", "track"] {
+            continue;
+        }
+
+        let value = match field.as_str() {
+            // Only the first location is used, the others are alternatives.
+            "location" if track.location.is_none() => &mut track.location,
+            "title" => &mut track.title,
+            "creator" => &mut track.creator,
+            "album" => &mut track.album,
+            "duration" => &mut track.duration,
+            "trackNum" => &mut track.track_number,
+            "image" => &mut track.image,
+            _ => continue,
+        };
+        value.get_or_insert_with(String::new).push_str(&text);
+    }
+
+    if !has_playlist {
+        return Err(PortableAudioLibraryError::InvalidPlaylist(
+            path.display().to_string(),
+        ));
+    }
+
+    Ok(tracks)
+}
+
+/// Resolves the location of a track to a local path, or `None` if it isn't a local file.
+///
+/// Relative locations are URI references, so they are percent-decoded before being resolved.
+fn resolve_location(location: &str, playlist_directory: &Path) -> Option<PathBuf> {
+    let location = location.trim();
+
+    match location.contains("://") {
+        true => resolve_entry(location, playlist_directory),
+        false => {
+            let path = location.strip_prefix("file:").unwrap_or(location);
+            resolve_entry(
+                &percent_decode_str(path).decode_utf8_lossy(),
+                playlist_directory,
+            )
+        }
+    }
+}
+
+/// Formats the audios as a .xspf file.
+fn format_xspf(playlist: &str, audios: &[&AudioMetadata]) -> PortableAudioLibraryResult<Vec<u8>> {
+    let mut writer = Writer::new_with_indent(vec![], b' ', 2);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer
+        .create_element("playlist")
+        .with_attributes([("version", "1"), ("xmlns", NAMESPACE)])
+        .write_inner_content(|writer| {
+            write_text_element(writer, "title", playlist)?;
+
+            writer
+                .create_element("trackList")
+                .write_inner_content(|writer| {
+                    for audio in audios {
+                        write_track(writer, audio)?;
+                    }
+
+                    Ok(())
+                })?;
+
+            Ok(())
+        })?;
+
+    let mut content = writer.into_inner();
+    content.push(b'\n');
+
+    Ok(content)
+}
+
+/// Writes a single track, with its elements in the order of the XSPF specification.
+///
+/// The location is a relative URI reference, which resolves to a `file://` URI next to the .xspf
+/// file.
+fn write_track(writer: &mut Writer<Vec<u8>>, audio: &AudioMetadata) -> std::io::Result<()> {
+    writer
+        .create_element("track")
+        .write_inner_content(|writer| {
+            let location = utf8_percent_encode(&audio.name, LOCATION).to_string();
+            write_text_element(writer, "location", &location)?;
+
+            if let Some(title) = &audio.title {
+                write_text_element(writer, "title", title)?;
+            }
+            if let Some(creator) = &audio.creator {
+                write_text_element(writer, "creator", creator)?;
+            }
+            if let Some(image) = &audio.image {
+                write_text_element(writer, "image", image)?;
+            }
+            if let Some(album) = &audio.album {
+                write_text_element(writer, "album", album)?;
+            }
+            if let Some(track_number) = audio.track_number {
+                write_text_element(writer, "trackNum", &track_number.to_string())?;
+            }
+            if let Some(duration) = audio.duration {
+                write_text_element(writer, "duration", &duration.as_millis().to_string())?;
+            }
+
+            Ok(())
+        })?;
+
+    Ok(())
+}
+
+/// Writes an element containing only text.
+fn write_text_element(writer: &mut Writer<Vec<u8>>, name: &str, text: &str) -> std::io::Result<()> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(text))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> PortableAudioLibraryResult<Vec<Track>> {
+        parse_xspf(content, Path::new("test.xspf"))
+    }
+
+    #[test]
+    fn parses_track_fields() {
+        let tracks = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Playlist title</title>
+              <trackList>
+                <track>
+                  <location>a%20b.mp3</location>
+                  <title>Tom &amp; Jerry</title>
+                  <creator>Someone</creator>
+                  <album><![CDATA[Best <of>]]></album>
+                  <duration>215000</duration>
+                  <trackNum>3</trackNum>
+                  <image>cover.jpg</image>
+                </track>
+              </trackList>
+            </playlist>"#,
+        )
+        .unwrap();
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].location.as_deref(), Some("a%20b.mp3"));
+        assert_eq!(tracks[0].title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(tracks[0].creator.as_deref(), Some("Someone"));
+        assert_eq!(tracks[0].album.as_deref(), Some("Best <of>"));
+        assert_eq!(tracks[0].duration.as_deref(), Some("215000"));
+        assert_eq!(tracks[0].track_number.as_deref(), Some("3"));
+        assert_eq!(tracks[0].image.as_deref(), Some("cover.jpg"));
+    }
+
+    #[test]
+    fn keeps_only_first_location() {
+        let tracks = parse(
+            r#"<playlist version="1" xmlns="http://xspf.org/ns/0/"><trackList>
+              <track><location>a.mp3</location><location>b.mp3</location></track>
+              <track/>
+            </trackList></playlist>"#,
+        )
+        .unwrap();
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].location.as_deref(), Some("a.mp3"));
+    }
+
+    #[test]
+    fn ignores_extensions() {
+        let tracks = parse(
+            r#"<playlist version="1" xmlns="http://xspf.org/ns/0/"><trackList><track>
+              <extension application="x"><title>Not the title</title></extension>
+              <title>Title</title>
+            </track></trackList></playlist>"#,
+        )
+        .unwrap();
+
+        assert_eq!(tracks[0].title.as_deref(), Some("Title"));
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(matches!(
+            parse("<html></html>"),
+            Err(PortableAudioLibraryError::InvalidPlaylist(_))
+        ));
+        assert!(matches!(
+            parse("<playlist><trackList></playlist>"),
+            Err(PortableAudioLibraryError::InvalidPlaylist(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn resolves_locations() {
+        let directory = Path::new("/music");
+
+        assert_eq!(
+            resolve_location("a%20b.mp3", directory),
+            Some(PathBuf::from("/music/a b.mp3"))
+        );
+        assert_eq!(
+            resolve_location("file://localhost/other/a.mp3", directory),
+            Some(PathBuf::from("/other/a.mp3"))
+        );
+        assert_eq!(
+            resolve_location("http://example.com/a.mp3", directory),
+            None
+        );
+    }
+}
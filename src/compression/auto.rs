@@ -0,0 +1,50 @@
+use super::{get_compression, CompressionType};
+use crate::error::PortableAudioLibraryResult;
+use std::io::Read;
+
+/// Size of the sample that is trial compressed.
+const SAMPLE_SIZE: u64 = 1024 * 1024;
+
+/// Compression types tried on the sample.
+const CANDIDATES: [CompressionType; 7] = [
+    CompressionType::Lz4(4),
+    CompressionType::Snap,
+    CompressionType::Gz(6),
+    CompressionType::Zstd(3),
+    CompressionType::Xz(6),
+    CompressionType::Brotli(5),
+    CompressionType::Bzip2(9),
+];
+
+/// Trial compresses a sample from the reader with every candidate and returns the compression
+/// type with the best ratio.
+///
+/// Returns [`CompressionType::None`] if no candidate saves more than `threshold` percent.
+pub fn select_compression_type(
+    reader: &mut impl Read,
+    threshold: u32,
+) -> PortableAudioLibraryResult<CompressionType> {
+    let mut sample = vec![];
+    reader.take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+
+    if sample.is_empty() {
+        return Ok(CompressionType::None);
+    }
+
+    let mut best = (CompressionType::None, sample.len());
+    for candidate in CANDIDATES {
+        let mut compressed_sample = vec![];
+        get_compression(&candidate).compress(&mut sample.as_slice(), &mut compressed_sample)?;
+
+        if compressed_sample.len() < best.1 {
+            best = (candidate, compressed_sample.len());
+        }
+    }
+
+    let saving = 100.0 * (1.0 - best.1 as f64 / sample.len() as f64);
+
+    match saving > threshold as f64 {
+        true => Ok(best.0),
+        false => Ok(CompressionType::None),
+    }
+}
@@ -0,0 +1,29 @@
+use super::Compression;
+use crate::error::PortableAudioLibraryResult;
+use brotli::{CompressorReader, Decompressor};
+use std::io::{copy, Read, Write};
+
+const BUFFER_SIZE: usize = 4096;
+const WINDOW_SIZE: u32 = 22;
+
+pub struct Brotli(pub u32);
+
+impl<R, W> Compression<R, W> for Brotli
+where
+    R: Read,
+    W: Write,
+{
+    fn compress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        let mut encoder = CompressorReader::new(reader, BUFFER_SIZE, self.0, WINDOW_SIZE);
+        copy(&mut encoder, writer)?;
+
+        Ok(())
+    }
+
+    fn decompress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        let mut decoder = Decompressor::new(reader, BUFFER_SIZE);
+        copy(&mut decoder, writer)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use super::Compression;
+use crate::error::PortableAudioLibraryResult;
+use bzip2::read::{BzDecoder, BzEncoder};
+use std::io::{copy, Read, Write};
+
+pub struct Bzip2(pub u32);
+
+impl<R, W> Compression<R, W> for Bzip2
+where
+    R: Read,
+    W: Write,
+{
+    fn compress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        // bzip2 only accepts levels from 1 to 9.
+        let level = bzip2::Compression::new(self.0.clamp(1, 9));
+        let mut encoder = BzEncoder::new(reader, level);
+        copy(&mut encoder, writer)?;
+
+        Ok(())
+    }
+
+    fn decompress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        let mut decoder = BzDecoder::new(reader);
+        copy(&mut decoder, writer)?;
+
+        Ok(())
+    }
+}
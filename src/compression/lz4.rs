@@ -15,6 +15,9 @@ where
         let mut encoder = EncoderBuilder::new().level(self.0).build(writer)?;
         copy(reader, &mut encoder)?;
 
+        let (_, result) = encoder.finish();
+        result?;
+
         Ok(())
     }
 
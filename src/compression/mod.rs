@@ -1,11 +1,19 @@
 use crate::error::PortableAudioLibraryResult;
 use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
 
+pub mod auto;
+pub mod brotli;
+pub mod bzip2;
 pub mod gz;
 pub mod lz4;
 pub mod none;
 pub mod snap;
+pub mod xz;
+pub mod zstd;
 
 /// Compression type.
 #[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -15,6 +23,63 @@ pub enum CompressionType {
     Lz4(u32),
     Snap,
     Gz(u32),
+    Zstd(i32),
+    Xz(u32),
+    Brotli(u32),
+    Bzip2(u32),
+
+    /// Picks the compression type with the best ratio for each audio, falling back to `None` if
+    /// it doesn't save more than the given percentage. Resolved when writing, so it is never
+    /// stored on an audio and behaves as `None` if used directly.
+    Auto(u32),
+}
+
+/// Extensions of audio formats that are already compressed.
+const COMPRESSED_FORMATS: [&str; 11] = [
+    "aac", "ape", "flac", "m4a", "mp3", "oga", "ogg", "opus", "webm", "wma", "wv",
+];
+
+/// Policy deciding which compression type is applied to each audio.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub enum CompressionPolicy {
+    /// Applies the chosen compression type to every audio.
+    #[default]
+    Uniform,
+
+    /// Stores already compressed formats without compression and applies the chosen compression
+    /// type to the rest.
+    Auto,
+
+    /// Keeps the compression type already set on each audio, ignoring the chosen one.
+    PerAudio,
+}
+
+impl CompressionPolicy {
+    /// Returns the compression type to apply to the audio with the given name and compression
+    /// type.
+    pub fn select(
+        &self,
+        compression_type: &CompressionType,
+        audio_compression_type: &CompressionType,
+        name: &str,
+    ) -> CompressionType {
+        match self {
+            CompressionPolicy::PerAudio => audio_compression_type.clone(),
+            CompressionPolicy::Auto if is_compressed_format(name) => CompressionType::None,
+            _ => compression_type.clone(),
+        }
+    }
+}
+
+/// Returns `true` if the audio's extension belongs to an already compressed format.
+pub fn is_compressed_format(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .map(|extension| {
+            let extension = extension.to_string_lossy().to_lowercase();
+            COMPRESSED_FORMATS.contains(&extension.as_str())
+        })
+        .unwrap_or(false)
 }
 
 /// Compresses and decompresses audio data.
@@ -41,5 +106,10 @@ where
         CompressionType::Lz4(level) => Box::new(lz4::Lz4(*level)),
         CompressionType::Snap => Box::new(snap::Snap),
         CompressionType::Gz(level) => Box::new(gz::Gz(*level)),
+        CompressionType::Zstd(level) => Box::new(zstd::Zstd(*level)),
+        CompressionType::Xz(level) => Box::new(xz::Xz(*level)),
+        CompressionType::Brotli(quality) => Box::new(brotli::Brotli(*quality)),
+        CompressionType::Bzip2(level) => Box::new(bzip2::Bzip2(*level)),
+        CompressionType::Auto(_) => Box::new(none::None),
     }
 }
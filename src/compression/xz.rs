@@ -0,0 +1,32 @@
+use super::Compression;
+use crate::error::PortableAudioLibraryResult;
+use std::io::{copy, Read, Write};
+use xz2::{
+    read::{XzDecoder, XzEncoder},
+    stream::{Check, Stream},
+};
+
+pub struct Xz(pub u32);
+
+impl<R, W> Compression<R, W> for Xz
+where
+    R: Read,
+    W: Write,
+{
+    fn compress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        // xz only accepts presets from 0 to 9.
+        let stream =
+            Stream::new_easy_encoder(self.0.min(9), Check::Crc64).map_err(std::io::Error::other)?;
+        let mut encoder = XzEncoder::new_stream(reader, stream);
+        copy(&mut encoder, writer)?;
+
+        Ok(())
+    }
+
+    fn decompress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        let mut decoder = XzDecoder::new(reader);
+        copy(&mut decoder, writer)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use super::Compression;
+use crate::error::PortableAudioLibraryResult;
+use std::io::{Read, Write};
+use zstd::stream::{copy_decode, copy_encode};
+
+pub struct Zstd(pub i32);
+
+impl<R, W> Compression<R, W> for Zstd
+where
+    R: Read,
+    W: Write,
+{
+    fn compress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        copy_encode(reader, writer, self.0)?;
+
+        Ok(())
+    }
+
+    fn decompress(&self, reader: &mut R, writer: &mut W) -> PortableAudioLibraryResult<()> {
+        copy_decode(reader, writer)?;
+
+        Ok(())
+    }
+}
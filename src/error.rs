@@ -9,4 +9,31 @@ pub enum PortableAudioLibraryError {
 
     #[error("Bincode error: {0}")]
     SerdeError(#[from] bincode::Error),
+
+    #[error("Invalid magic number, not a PAL file")]
+    InvalidMagic,
+
+    #[error("Unsupported PAL format version: {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("Metadata size of {0} bytes exceeds the limit, the file is corrupted")]
+    MetadataTooLarge(u64),
+
+    #[error("Invalid audio name, it must be a plain file name: {0}")]
+    InvalidAudioName(String),
+
+    #[error("Checksum mismatch for audio: {0}")]
+    ChecksumMismatch(String),
+
+    #[error("Audio not found: {0}")]
+    AudioNotFound(String),
+
+    #[error("Playlist not found: {0}")]
+    PlaylistNotFound(String),
+
+    #[error("Audio is compressed and can't be accessed in place: {0}")]
+    AudioCompressed(String),
+
+    #[error("Invalid playlist: {0}")]
+    InvalidPlaylist(String),
 }
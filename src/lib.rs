@@ -24,12 +24,75 @@
 //! ```
 //! use portable_audio_library::{tempfile, serialization::Metadata};
 //! use portable_audio_library::builder::directory::build_directory_from_metadata;
+//! # use portable_audio_library::builder::directory::build_metadata_from_directory;
+//! # let library_directory = tempfile::tempdir().unwrap();
+//! # let library_path = library_directory.path().join("example-library.pal");
+//! # let mut metadata = build_metadata_from_directory("doc-tests/example-library").unwrap();
+//! # metadata.write_to_file(&library_path).unwrap();
 //!
 //! let temporary_directory = tempfile::tempdir().unwrap();
-//! let metadata = Metadata::read_from_file("doc-tests/example-library.pal", temporary_directory.path()).unwrap();
+//! let metadata = Metadata::read_from_file(&library_path, temporary_directory.path()).unwrap();
 //!
 //! build_directory_from_metadata("doc-tests/out-library", &metadata).unwrap();
 //! ```
+//!
+//! ## Read only the metadata of a PAL file
+//!
+//! ```
+//! use portable_audio_library::serialization::Metadata;
+//! # use portable_audio_library::builder::directory::build_metadata_from_directory;
+//! # let temporary_directory = portable_audio_library::tempfile::tempdir().unwrap();
+//! # let library_path = temporary_directory.path().join("example-library.pal");
+//! # let mut metadata = build_metadata_from_directory("doc-tests/example-library").unwrap();
+//! # metadata.write_to_file(&library_path).unwrap();
+//!
+//! let metadata = Metadata::read_header(&library_path).unwrap();
+//! ```
+//!
+//! ## Write and read a PAL file in memory
+//!
+//! ```
+//! use portable_audio_library::builder::directory::build_metadata_from_directory;
+//! use portable_audio_library::serialization::{Metadata, Options};
+//! use std::io::Cursor;
+//!
+//! let mut metadata = build_metadata_from_directory("doc-tests/example-library").unwrap();
+//! let mut buffer = Cursor::new(vec![]);
+//! metadata.write_to_seekable(&mut buffer, &Options::default()).unwrap();
+//!
+//! buffer.set_position(0);
+//! let mut audio = vec![];
+//! Metadata::extract_audio_from(buffer, "music1.mp3", &mut audio).unwrap();
+//! ```
+//!
+//! ## Access an uncompressed audio in place
+//!
+//! ```
+//! use portable_audio_library::serialization::MappedLibrary;
+//! # use portable_audio_library::builder::directory::build_metadata_from_directory;
+//! # let temporary_directory = portable_audio_library::tempfile::tempdir().unwrap();
+//! # let library_path = temporary_directory.path().join("example-library.pal");
+//! # let mut metadata = build_metadata_from_directory("doc-tests/example-library").unwrap();
+//! # metadata.write_to_file(&library_path).unwrap();
+//!
+//! // SAFETY: Nothing else modifies the library while it is mapped.
+//! let library = unsafe { MappedLibrary::open(&library_path) }.unwrap();
+//! let audio: &[u8] = library.audio("music1.mp3").unwrap();
+//! ```
+//!
+//! ## Extract a single audio from a PAL file
+//!
+//! ```
+//! use portable_audio_library::serialization::Metadata;
+//! # use portable_audio_library::builder::directory::build_metadata_from_directory;
+//! # let temporary_directory = portable_audio_library::tempfile::tempdir().unwrap();
+//! # let library_path = temporary_directory.path().join("example-library.pal");
+//! # let mut metadata = build_metadata_from_directory("doc-tests/example-library").unwrap();
+//! # metadata.write_to_file(&library_path).unwrap();
+//!
+//! let mut audio = vec![];
+//! Metadata::extract_audio(&library_path, "music1.mp3", &mut audio).unwrap();
+//! ```
 //
 
 pub use tempfile;
@@ -37,4 +100,5 @@ pub use tempfile;
 pub mod builder;
 pub mod compression;
 pub mod error;
+pub mod progress;
 pub mod serialization;
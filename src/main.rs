@@ -1,16 +1,31 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
 use portable_audio_library::{
-    builder::{directory, m3u},
+    builder::{directory, m3u, pls, xspf},
     compression,
-    error::PortableAudioLibraryResult,
-    serialization::Metadata,
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    progress::{NoProgress, Progress, Stage},
+    serialization::{Metadata, Options, FORMAT_VERSION},
     tempfile,
 };
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::{Arc, Mutex},
+};
+
+/// Path that refers to stdin or stdout.
+const STDIO: &str = "-";
+
+const PROGRESS_BAR_TEMPLATE: &str = "{spinner:.green} {msg} [{wide_bar}] {pos}/{len} ({eta})";
+const PROGRESS_CHARS: &str = "=> ";
+const CHECK_GREEN: &str = "\x1b[32m✓\x1b[0m";
 
 #[derive(Parser)]
 struct Cli {
     #[clap(subcommand)]
     subcommand: Subcommands,
+
+    #[clap(short, long, global = true, help = "Don't print progress bars.")]
+    quiet: bool,
 }
 
 #[derive(Subcommand)]
@@ -20,7 +35,7 @@ enum Subcommands {
         #[arg()]
         input: String,
 
-        #[arg()]
+        #[arg(help = "Path of the .pal file, or `-` for stdout.")]
         output: String,
 
         #[clap(
@@ -43,14 +58,29 @@ enum Subcommands {
             short = 'l',
             long,
             default_value = "0",
-            help = "Level of compression. This only works in some compression types."
+            help = "Level of compression. This only works in some compression types. For `auto`, this is the minimum percentage of space saved."
         )]
         compression_level: u32,
+
+        #[clap(
+            long,
+            default_value = "uniform",
+            help = "Policy deciding which audio files are compressed. `auto` skips already compressed formats."
+        )]
+        compression_policy: CompressionPolicy,
+
+        #[clap(
+            short,
+            long,
+            default_value = "1",
+            help = "Number of threads used to compress audio files. 0 uses one per CPU."
+        )]
+        jobs: usize,
     },
 
     #[clap(about = "Decode a .pal file.")]
     Decode {
-        #[arg()]
+        #[arg(help = "Path of the .pal file, or `-` for stdin.")]
         input: String,
 
         #[arg()]
@@ -63,6 +93,64 @@ enum Subcommands {
             help = "Refers to what kind of audio library that needs to be converted from a .pal file."
         )]
         builder: BuilderType,
+
+        #[clap(
+            short,
+            long,
+            default_value = "1",
+            help = "Number of threads used to decompress audio files. 0 uses one per CPU."
+        )]
+        jobs: usize,
+    },
+
+    #[clap(about = "Extract selected audio files from a .pal file.")]
+    Extract {
+        #[arg()]
+        input: String,
+
+        #[arg(
+            required_unless_present = "playlists",
+            help = "Names or glob patterns of the audio files to extract."
+        )]
+        patterns: Vec<String>,
+
+        #[clap(
+            short,
+            long = "playlist",
+            help = "Extract every audio file of the playlist."
+        )]
+        playlists: Vec<String>,
+
+        #[clap(
+            short,
+            long,
+            help = "Directory to extract to. A single audio file is written to stdout if omitted."
+        )]
+        output: Option<String>,
+    },
+
+    #[clap(about = "Verify the integrity of a .pal file.")]
+    Verify {
+        #[arg()]
+        input: String,
+    },
+
+    #[clap(about = "List the audio files of a .pal file.")]
+    List {
+        #[arg()]
+        input: String,
+
+        #[clap(long, help = "Print the output as JSON.")]
+        json: bool,
+    },
+
+    #[clap(about = "Print information about a .pal file.")]
+    Info {
+        #[arg()]
+        input: String,
+
+        #[clap(long, help = "Print the output as JSON.")]
+        json: bool,
     },
 }
 
@@ -71,6 +159,8 @@ enum BuilderType {
     #[default]
     Directory,
     M3U,
+    Pls,
+    Xspf,
 }
 
 #[derive(ValueEnum, Clone)]
@@ -79,6 +169,26 @@ enum CompressionType {
     Lz4,
     Snap,
     Gz,
+    Zstd,
+    Xz,
+    Brotli,
+    Bzip2,
+    Auto,
+}
+
+#[derive(ValueEnum, Clone)]
+enum CompressionPolicy {
+    Uniform,
+    Auto,
+}
+
+impl From<&CompressionPolicy> for compression::CompressionPolicy {
+    fn from(compression_policy: &CompressionPolicy) -> Self {
+        match compression_policy {
+            CompressionPolicy::Uniform => compression::CompressionPolicy::Uniform,
+            CompressionPolicy::Auto => compression::CompressionPolicy::Auto,
+        }
+    }
 }
 
 impl CompressionType {
@@ -88,6 +198,54 @@ impl CompressionType {
             CompressionType::Lz4 => compression::CompressionType::Lz4(compression_level),
             CompressionType::Snap => compression::CompressionType::Snap,
             CompressionType::Gz => compression::CompressionType::Gz(compression_level),
+            CompressionType::Zstd => compression::CompressionType::Zstd(compression_level as i32),
+            CompressionType::Xz => compression::CompressionType::Xz(compression_level),
+            CompressionType::Brotli => compression::CompressionType::Brotli(compression_level),
+            CompressionType::Bzip2 => compression::CompressionType::Bzip2(compression_level),
+            CompressionType::Auto => compression::CompressionType::Auto(compression_level),
+        }
+    }
+}
+
+/// Renders progress reports as a progress bar on stderr.
+#[derive(Default)]
+struct ProgressBar {
+    progress_bar: Mutex<Option<indicatif::ProgressBar>>,
+}
+
+impl Progress for ProgressBar {
+    fn started(&self, stage: Stage, audio_count: u64) {
+        let progress_bar = indicatif::ProgressBar::new(audio_count);
+        progress_bar.set_style(
+            indicatif::ProgressStyle::default_bar()
+                .template(PROGRESS_BAR_TEMPLATE)
+                .unwrap()
+                .progress_chars(PROGRESS_CHARS),
+        );
+        progress_bar.set_message(match stage {
+            Stage::Writing => "Compressing audio files",
+            Stage::Reading => "Decompressing audio files",
+            Stage::Verifying => "Verifying audio files",
+        });
+
+        *self.progress_bar.lock().unwrap() = Some(progress_bar);
+    }
+
+    fn progressed(&self, _name: &str, _bytes: u64) {
+        if let Some(progress_bar) = &*self.progress_bar.lock().unwrap() {
+            progress_bar.inc(1);
+        }
+    }
+
+    fn finished(&self, stage: Stage) {
+        if let Some(progress_bar) = self.progress_bar.lock().unwrap().take() {
+            progress_bar.finish_and_clear();
+        }
+
+        match stage {
+            Stage::Writing => eprintln!("{} Writing done!", CHECK_GREEN),
+            Stage::Reading => eprintln!("{} Decompression done!", CHECK_GREEN),
+            Stage::Verifying => {}
         }
     }
 }
@@ -95,6 +253,15 @@ impl CompressionType {
 fn main() -> PortableAudioLibraryResult<()> {
     let cli = Cli::parse();
 
+    let progress: Arc<dyn Progress> = match cli.quiet {
+        true => Arc::new(NoProgress),
+        false => Arc::new(ProgressBar::default()),
+    };
+    let options = |jobs: usize| Options {
+        jobs,
+        progress: progress.clone(),
+    };
+
     match &cli.subcommand {
         Subcommands::Encode {
             input,
@@ -102,27 +269,57 @@ fn main() -> PortableAudioLibraryResult<()> {
             builder,
             compression_type,
             compression_level,
+            compression_policy,
+            jobs,
         } => match builder {
             BuilderType::Directory => {
                 let mut metadata = directory::build_metadata_from_directory(input)?;
                 metadata.compression_type = compression_type.into(*compression_level);
+                metadata.compression_policy = compression_policy.into();
 
-                metadata.write_to_file(output)?;
+                write_metadata(&mut metadata, output, &options(*jobs))?;
             }
             BuilderType::M3U => {
                 let mut metadata = m3u::build_metadata_from_m3u(input)?;
                 metadata.compression_type = compression_type.into(*compression_level);
+                metadata.compression_policy = compression_policy.into();
+
+                write_metadata(&mut metadata, output, &options(*jobs))?;
+            }
+            BuilderType::Pls => {
+                let mut metadata = pls::build_metadata_from_pls(input)?;
+                metadata.compression_type = compression_type.into(*compression_level);
+                metadata.compression_policy = compression_policy.into();
+
+                write_metadata(&mut metadata, output, &options(*jobs))?;
+            }
+            BuilderType::Xspf => {
+                let mut metadata = xspf::build_metadata_from_xspf(input)?;
+                metadata.compression_type = compression_type.into(*compression_level);
+                metadata.compression_policy = compression_policy.into();
 
-                metadata.write_to_file(output)?;
+                write_metadata(&mut metadata, output, &options(*jobs))?;
             }
         },
         Subcommands::Decode {
             input,
             output,
             builder,
+            jobs,
         } => {
             let directory_store = tempfile::tempdir()?;
-            let metadata = Metadata::read_from_file(input, directory_store.path())?;
+            let metadata = match input.as_str() {
+                STDIO => Metadata::read_from_stream(
+                    std::io::stdin().lock(),
+                    directory_store.path(),
+                    &options(*jobs),
+                )?,
+                _ => Metadata::read_from_file_with_options(
+                    input,
+                    directory_store.path(),
+                    &options(*jobs),
+                )?,
+            };
 
             match builder {
                 BuilderType::Directory => {
@@ -131,9 +328,227 @@ fn main() -> PortableAudioLibraryResult<()> {
                 BuilderType::M3U => {
                     m3u::build_m3u_from_metadata(output, &metadata)?;
                 }
+                BuilderType::Pls => {
+                    pls::build_pls_from_metadata(output, &metadata)?;
+                }
+                BuilderType::Xspf => {
+                    xspf::build_xspf_from_metadata(output, &metadata)?;
+                }
+            }
+        }
+        Subcommands::Extract {
+            input,
+            patterns,
+            playlists,
+            output,
+        } => {
+            let metadata = Metadata::read_header(input)?;
+            let names = select_audios(&metadata, patterns, playlists)?;
+
+            match output {
+                Some(output) => {
+                    Metadata::extract_audios(input, &names, output)?;
+                }
+                None => {
+                    if names.len() != 1 {
+                        Cli::command()
+                            .error(
+                                ErrorKind::ArgumentConflict,
+                                format!(
+                                    "{} audio files matched, only one can be written to stdout. Use --output instead.",
+                                    names.len()
+                                ),
+                            )
+                            .exit();
+                    }
+
+                    Metadata::extract_audio(input, &names[0], &mut std::io::stdout().lock())?;
+                }
             }
         }
+        Subcommands::Verify { input } => {
+            let report = Metadata::verify_file_with_options(input, &options(1))?;
+
+            match report.expected_size {
+                Some(expected_size) if expected_size != report.actual_size => println!(
+                    "File size mismatch: expected {} bytes, found {} bytes",
+                    expected_size, report.actual_size
+                ),
+                Some(_) => {}
+                None => println!(
+                    "File size mismatch: audio sizes overflow, found {} bytes",
+                    report.actual_size
+                ),
+            }
+
+            for (name, error) in &report.failures {
+                println!("{}: {}", name, error);
+            }
+
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+
+            println!("Verification done! No problems found.");
+        }
+        Subcommands::List { input, json } => {
+            let metadata = Metadata::read_header(input)?;
+
+            list(&metadata, *json);
+        }
+        Subcommands::Info { input, json } => {
+            let metadata = Metadata::read_header(input)?;
+
+            info(&metadata, *json);
+        }
     }
 
     Ok(())
 }
+
+fn write_metadata(
+    metadata: &mut Metadata,
+    output: &str,
+    options: &Options,
+) -> PortableAudioLibraryResult<()> {
+    match output {
+        STDIO => metadata.write_to(std::io::stdout().lock(), options),
+        _ => metadata.write_to_file_with_options(output, options),
+    }
+}
+
+fn select_audios(
+    metadata: &Metadata,
+    patterns: &[String],
+    playlists: &[String],
+) -> PortableAudioLibraryResult<Vec<String>> {
+    let glob_patterns: Vec<glob::Pattern> = patterns
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern).unwrap_or_else(|error| {
+                Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!("Invalid pattern '{}': {}", pattern, error),
+                    )
+                    .exit()
+            })
+        })
+        .collect();
+
+    for (pattern, glob_pattern) in patterns.iter().zip(&glob_patterns) {
+        if !metadata
+            .audios
+            .iter()
+            .any(|audio| &audio.name == pattern || glob_pattern.matches(&audio.name))
+        {
+            return Err(PortableAudioLibraryError::AudioNotFound(pattern.clone()));
+        }
+    }
+
+    for playlist in playlists {
+        if !metadata
+            .playlists
+            .iter()
+            .any(|metadata_playlist| &metadata_playlist.name == playlist)
+        {
+            return Err(PortableAudioLibraryError::PlaylistNotFound(
+                playlist.clone(),
+            ));
+        }
+    }
+
+    let playlist_tracks: HashSet<&str> = metadata
+        .playlists
+        .iter()
+        .filter(|playlist| playlists.contains(&playlist.name))
+        .flat_map(|playlist| &playlist.tracks)
+        .map(String::as_str)
+        .collect();
+
+    Ok(metadata
+        .audios
+        .iter()
+        .filter(|audio| {
+            patterns.contains(&audio.name)
+                || glob_patterns
+                    .iter()
+                    .any(|glob_pattern| glob_pattern.matches(&audio.name))
+                || playlist_tracks.contains(audio.name.as_str())
+        })
+        .map(|audio| audio.name.clone())
+        .collect())
+}
+
+fn list(metadata: &Metadata, json: bool) {
+    let playlists_by_audio = metadata.playlists_by_audio();
+    let playlist_names =
+        |name: &str| -> Vec<&str> { playlists_by_audio.get(name).cloned().unwrap_or_default() };
+
+    match json {
+        true => {
+            let audios: Vec<json::JsonValue> = metadata
+                .audios
+                .iter()
+                .map(|audio| {
+                    json::object! {
+                        name: audio.name.clone(),
+                        size: audio.size,
+                        compression_type: format!("{:?}", audio.compression_type),
+                        playlists: playlist_names(&audio.name),
+                    }
+                })
+                .collect();
+
+            println!("{}", json::stringify_pretty(audios, 4));
+        }
+        false => {
+            for audio in &metadata.audios {
+                println!(
+                    "{}\t{}\t{:?}\t{}",
+                    audio.name,
+                    audio.size,
+                    audio.compression_type,
+                    playlist_names(&audio.name).join(", ")
+                );
+            }
+        }
+    }
+}
+
+fn info(metadata: &Metadata, json: bool) {
+    let playlists: BTreeSet<_> = metadata
+        .playlists
+        .iter()
+        .map(|playlist| &playlist.name)
+        .collect();
+    // Sizes come from the file, so a corrupted one must not overflow the total.
+    let compressed_size = metadata
+        .audios
+        .iter()
+        .fold(0u64, |size, audio| size.saturating_add(audio.size));
+    let compression_type = format!("{:?}", metadata.compression_type);
+
+    match json {
+        true => {
+            let info = json::object! {
+                name: metadata.name.clone(),
+                format_version: FORMAT_VERSION,
+                compression_type: compression_type,
+                audio_count: metadata.audios.len(),
+                playlist_count: playlists.len(),
+                compressed_size: compressed_size,
+            };
+
+            println!("{}", json::stringify_pretty(info, 4));
+        }
+        false => {
+            println!("Name: {}", metadata.name);
+            println!("Format version: {}", FORMAT_VERSION);
+            println!("Compression type: {}", compression_type);
+            println!("Audio files: {}", metadata.audios.len());
+            println!("Playlists: {}", playlists.len());
+            println!("Compressed size: {} bytes", compressed_size);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Progress reporting for long running operations on PAL files.
+
+/// Operation reported to a [`Progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Compressing audios into a PAL file.
+    Writing,
+
+    /// Decompressing audios from a PAL file.
+    Reading,
+
+    /// Verifying the audios of a PAL file.
+    Verifying,
+}
+
+/// Receives progress reports while a PAL file is written, read or verified.
+///
+/// Every method does nothing by default. Reports can come from several worker threads at once.
+pub trait Progress: Send + Sync {
+    /// Called when the stage starts, with the number of audios it will process.
+    fn started(&self, _stage: Stage, _audio_count: u64) {}
+
+    /// Called after an audio is processed, with the size of its compressed data in bytes.
+    fn progressed(&self, _name: &str, _bytes: u64) {}
+
+    /// Called when the stage finishes successfully.
+    fn finished(&self, _stage: Stage) {}
+}
+
+/// Progress that ignores every report.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
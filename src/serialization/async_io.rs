@@ -0,0 +1,135 @@
+use super::{
+    reader::{check_metadata_size, read_metadata, read_signature},
+    AudioMetadata, Metadata, Options, SIGNATURE_SIZE, U64_SIZE,
+};
+use crate::error::PortableAudioLibraryResult;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio_util::io::SyncIoBridge;
+
+impl Metadata {
+    /// Reads only the metadata from the async reader, without decompressing any audio.
+    ///
+    /// The offset of each audio is filled in, while the path is left empty.
+    pub async fn read_header_async(
+        mut reader: impl AsyncRead + Unpin,
+    ) -> PortableAudioLibraryResult<Metadata> {
+        let mut header = vec![0; SIGNATURE_SIZE + U64_SIZE];
+        reader.read_exact(&mut header).await?;
+
+        // The signature is validated before trusting the metadata size.
+        read_signature(&mut &header[..SIGNATURE_SIZE])?;
+        let metadata_size = u64::from_be_bytes(header[SIGNATURE_SIZE..].try_into().unwrap());
+
+        check_metadata_size(metadata_size)?;
+
+        // Reading through `take` only allocates as much as the reader actually holds.
+        (&mut reader)
+            .take(metadata_size)
+            .read_to_end(&mut header)
+            .await?;
+
+        read_metadata(&mut header.as_slice())
+    }
+
+    /// Reads the metadata from the async reader, decompressing every audio into the directory
+    /// store in the order they are stored.
+    pub async fn read_from_async(
+        reader: impl AsyncRead + Unpin + Send + 'static,
+        directory_store: impl Into<PathBuf>,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<Metadata> {
+        let directory_store: PathBuf = directory_store.into();
+        let options = options.clone();
+        let reader = SyncIoBridge::new(reader);
+
+        tokio::task::spawn_blocking(move || {
+            Self::read_from_stream(reader, directory_store, &options)
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    /// Extracts a single audio from the async reader to the async writer, decompressing it while
+    /// it is streamed.
+    ///
+    /// The reader doesn't need to seek, so the audio can be served straight from a request body.
+    pub async fn extract_audio_async(
+        reader: impl AsyncRead + Unpin + Send + 'static,
+        name: &str,
+        writer: impl AsyncWrite + Unpin + Send + 'static,
+    ) -> PortableAudioLibraryResult<AudioMetadata> {
+        let name = name.to_string();
+        let reader = SyncIoBridge::new(reader);
+        let mut writer = SyncIoBridge::new(writer);
+
+        tokio::task::spawn_blocking(move || {
+            let audio_metadata = Self::extract_audio_from_stream(reader, &name, &mut writer)?;
+            writer.shutdown()?;
+
+            Ok(audio_metadata)
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    /// Writes the metadata to the async writer.
+    ///
+    /// The audios are compressed into a single temporary file first, like [`Metadata::write_to`].
+    pub async fn write_to_async(
+        &mut self,
+        writer: impl AsyncWrite + Unpin + Send + 'static,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<()> {
+        let mut metadata = self.clone();
+        let options = options.clone();
+        let mut writer = SyncIoBridge::new(writer);
+
+        *self = tokio::task::spawn_blocking(move || {
+            metadata.write_to(&mut writer, &options)?;
+            writer.shutdown()?;
+
+            PortableAudioLibraryResult::Ok(metadata)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::directory::build_metadata_from_directory;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn extracts_audio_written_through_duplex() {
+        let mut metadata = build_metadata_from_directory("doc-tests/example-library").unwrap();
+        let options = Options::default();
+
+        let (writer, mut reader) = tokio::io::duplex(64 * 1024);
+        let mut library = vec![];
+        let (written, read) = tokio::join!(
+            metadata.write_to_async(writer, &options),
+            reader.read_to_end(&mut library),
+        );
+        written.unwrap();
+        read.unwrap();
+
+        let (writer, mut reader) = tokio::io::duplex(64 * 1024);
+        let mut audio = vec![];
+        let (extracted, read) = tokio::join!(
+            Metadata::extract_audio_async(Cursor::new(library), "music2.mp3", writer),
+            reader.read_to_end(&mut audio),
+        );
+        assert_eq!(extracted.unwrap().name, "music2.mp3");
+        read.unwrap();
+
+        assert_eq!(
+            audio,
+            std::fs::read("doc-tests/example-library/music2.mp3").unwrap()
+        );
+    }
+}
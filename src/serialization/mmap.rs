@@ -0,0 +1,67 @@
+use super::{reader::read_metadata, Metadata};
+use crate::{
+    compression::CompressionType,
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+};
+use memmap2::Mmap;
+use std::path::PathBuf;
+
+/// PAL file mapped into memory, giving access to uncompressed audios in place.
+pub struct MappedLibrary {
+    mmap: Mmap,
+    metadata: Metadata,
+}
+
+impl MappedLibrary {
+    /// Maps the file into memory and reads its metadata.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, truncated or removed, in this process or another one, for
+    /// as long as the returned library is alive. See [`Mmap::map`] for details.
+    pub unsafe fn open(path: impl Into<PathBuf>) -> PortableAudioLibraryResult<MappedLibrary> {
+        let path = path.into();
+
+        let portable_audio_library_file = std::fs::File::open(path)?;
+        // SAFETY: The mapping is read-only, and the caller upholds the contract of this function
+        // that the file isn't modified while it is mapped.
+        let mmap = unsafe { Mmap::map(&portable_audio_library_file)? };
+        let metadata = read_metadata(&mut &mmap[..])?;
+
+        Ok(MappedLibrary { mmap, metadata })
+    }
+
+    /// Returns the metadata of the library.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the data of the audio straight from the mapped file.
+    ///
+    /// Only audios stored with [`CompressionType::None`] can be accessed this way. The checksum of
+    /// the audio is not verified.
+    pub fn audio(&self, name: &str) -> PortableAudioLibraryResult<&[u8]> {
+        let audio_metadata = self.metadata.find_audio(name)?;
+
+        if audio_metadata.compression_type != CompressionType::None {
+            return Err(PortableAudioLibraryError::AudioCompressed(name.to_string()));
+        }
+
+        let truncated = || {
+            std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("Audio data of {} is truncated", name),
+            )
+            .into()
+        };
+
+        // The offset and the size come from the file, so they can't be trusted to fit.
+        let start = usize::try_from(audio_metadata.offset).map_err(|_| truncated())?;
+        let end = usize::try_from(audio_metadata.size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .ok_or_else(truncated)?;
+
+        self.mmap.get(start..end).ok_or_else(truncated)
+    }
+}
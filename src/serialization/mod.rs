@@ -1,164 +1,196 @@
 use crate::{
-    compression::{get_compression, CompressionType},
-    error::PortableAudioLibraryResult,
+    compression::{CompressionPolicy, CompressionType},
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    progress::{NoProgress, Progress},
 };
 use serde::{Deserialize, Serialize};
-use std::{
-    fs::File,
-    io::{Read, Seek, Write},
-    path::PathBuf,
-};
-use tempfile::tempfile;
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+
+#[cfg(feature = "async")]
+mod async_io;
+mod checksum;
+mod mmap;
+mod parallel;
+mod reader;
+mod verify;
+mod writer;
+
+pub use checksum::Checksum;
+pub use mmap::MappedLibrary;
+pub use verify::VerificationReport;
 
-/// Playlist name.
-pub type Playlist = String;
+/// Signature at the start of every PAL file.
+pub const MAGIC: [u8; 4] = *b"PAL\0";
 
+/// Version of the PAL file format written by this crate.
+pub const FORMAT_VERSION: u16 = 6;
+
+const SIGNATURE_SIZE: usize = MAGIC.len() + std::mem::size_of::<u16>();
 const U64_SIZE: usize = std::mem::size_of::<u64>();
-const PROGRESS_BAR_TEMPLATE: &str = "{spinner:.green} {msg} [{wide_bar}] {pos}/{len} ({eta})";
-const PROGRESS_CHARS: &str = "=> ";
-const CHECK_GREEN: &str = "\x1b[32m✓\x1b[0m";
 const CHUNK_SIZE: usize = 16 * 1024 * 1024;
 
+/// Largest metadata size accepted when reading, far above what any real library needs.
+const MAX_METADATA_SIZE: u64 = 256 * 1024 * 1024;
+
 /// Metadata of audio library.
 #[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Metadata {
     pub name: String,
     pub compression_type: CompressionType,
     pub audios: Vec<AudioMetadata>,
-}
 
-/// Metadata of audio.
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
-pub struct AudioMetadata {
-    pub name: String,
-    pub size: u64,
+    /// Playlists of the library, in the order they were added.
     pub playlists: Vec<Playlist>,
 
+    /// Policy deciding which audios are compressed with the compression type.
     #[serde(skip_serializing, skip_deserializing)]
-    pub path: PathBuf,
+    pub compression_policy: CompressionPolicy,
 }
 
-impl Metadata {
-    /// Writes the metadata to the file.
-    pub fn write_to_file(&mut self, path: impl Into<PathBuf>) -> PortableAudioLibraryResult<()> {
-        let path = path.into();
+/// Metadata of audio.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct AudioMetadata {
+    pub name: String,
+    pub size: u64,
 
-        let compression = get_compression(&self.compression_type);
-        let mut compressed_audio_files = vec![];
+    /// Display title of the audio, if the source playlist provided one.
+    pub title: Option<String>,
 
-        let progress_bar = indicatif::ProgressBar::new(self.audios.len() as u64);
-        progress_bar.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template(PROGRESS_BAR_TEMPLATE)
-                .unwrap()
-                .progress_chars(PROGRESS_CHARS),
-        );
-        progress_bar.set_message("Compressing audio files");
+    /// Duration of the audio, if the source playlist provided one.
+    pub duration: Option<Duration>,
 
-        for audio in &mut self.audios {
-            let mut audio_file = std::fs::File::open(&audio.path)?;
-            let mut compressed_audio_file = tempfile()?;
+    /// Artist of the audio, if the source playlist provided one.
+    pub creator: Option<String>,
 
-            compression.compress(&mut audio_file, &mut compressed_audio_file)?;
+    /// Album of the audio, if the source playlist provided one.
+    pub album: Option<String>,
 
-            audio.size = compressed_audio_file.metadata()?.len();
-            compressed_audio_files.push(compressed_audio_file);
+    /// Position of the audio on its album, if the source playlist provided one.
+    pub track_number: Option<u32>,
 
-            progress_bar.inc(1);
-        }
+    /// URI of the cover image of the audio, if the source playlist provided one.
+    pub image: Option<String>,
 
-        progress_bar.finish_and_clear();
-        println!("{} Compression done!", CHECK_GREEN);
-
-        let progress_bar = indicatif::ProgressBar::new(compressed_audio_files.len() as u64 + 1);
-        progress_bar.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template(PROGRESS_BAR_TEMPLATE)
-                .unwrap()
-                .progress_chars(PROGRESS_CHARS),
-        );
-        progress_bar.set_message("Writing to file");
-
-        let mut portable_audio_library_file = std::fs::File::create(path)?;
-        let serialized_portable_audio_library = bincode::serialize(&self)?;
-        let metadata_size = serialized_portable_audio_library.len() as u64;
-        portable_audio_library_file.write_all(&metadata_size.to_be_bytes())?;
-        portable_audio_library_file.write_all(&serialized_portable_audio_library)?;
-        progress_bar.inc(1);
-
-        for mut compressed_audio_file in &compressed_audio_files {
-            compressed_audio_file.seek(std::io::SeekFrom::Start(0))?;
-            loop {
-                let mut chunk = vec![0; CHUNK_SIZE]; // 16 MB chunk
-                let bytes_read = compressed_audio_file.read(&mut chunk)?;
-
-                if bytes_read == 0 {
-                    break;
-                }
+    /// Compression type applied to this audio.
+    ///
+    /// When writing, this is replaced by the one chosen by the library's compression policy,
+    /// unless the policy is [`CompressionPolicy::PerAudio`].
+    pub compression_type: CompressionType,
 
-                portable_audio_library_file.write_all(&chunk[..bytes_read])?;
-            }
+    /// SHA-256 digest of the original audio file.
+    pub checksum: Option<Checksum>,
 
-            progress_bar.inc(1);
-        }
+    /// SHA-256 digest of the compressed audio data.
+    pub compressed_checksum: Option<Checksum>,
 
-        progress_bar.finish_and_clear();
-        println!("{} Writing done!", CHECK_GREEN);
+    #[serde(skip_serializing, skip_deserializing)]
+    pub path: PathBuf,
 
-        Ok(())
-    }
+    /// Offset of the compressed audio data from the start of the PAL data.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub offset: u64,
+}
 
-    /// Reads the metadata from the file.
-    pub fn read_from_file(
-        path: impl Into<PathBuf>,
-        directory_store: impl Into<PathBuf>,
-    ) -> PortableAudioLibraryResult<Metadata> {
-        let path = path.into();
-        let directory_store: PathBuf = directory_store.into();
+/// Playlist of the library.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
 
-        let mut portable_audio_library_file = std::fs::File::open(path)?;
+    /// Names of the audios of the playlist, in order. The same audio may appear more than once.
+    pub tracks: Vec<String>,
+}
 
-        let mut metadata_size_buf = vec![0; U64_SIZE];
-        portable_audio_library_file.read_exact(&mut metadata_size_buf)?;
-        let metadata_size = u64::from_be_bytes(metadata_size_buf.try_into().unwrap());
+/// Options for writing and reading PAL files.
+#[derive(Clone)]
+pub struct Options {
+    /// Number of worker threads used to compress or decompress audios. `0` uses one per
+    /// available CPU.
+    pub jobs: usize,
 
-        let mut metadata_buf = vec![0; metadata_size as usize];
-        portable_audio_library_file.read_exact(&mut metadata_buf)?;
-        let mut metadata: Metadata = bincode::deserialize(&metadata_buf)?;
+    /// Receives progress reports while audios are compressed, decompressed or verified.
+    pub progress: Arc<dyn Progress>,
+}
 
-        let compression_type = &metadata.compression_type;
-        let compression = get_compression(compression_type);
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            progress: Arc::new(NoProgress),
+        }
+    }
+}
 
-        let progress_bar = indicatif::ProgressBar::new(metadata.audios.len() as u64);
-        progress_bar.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template(PROGRESS_BAR_TEMPLATE)
-                .unwrap()
-                .progress_chars(PROGRESS_CHARS),
-        );
-        progress_bar.set_message("Decompressing audio files");
+impl std::fmt::Debug for Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Options")
+            .field("jobs", &self.jobs)
+            .finish_non_exhaustive()
+    }
+}
 
-        let mut offset = metadata_size + U64_SIZE as u64;
-        for audio_metadata in &mut metadata.audios {
-            portable_audio_library_file.seek(std::io::SeekFrom::Start(offset))?;
-            let mut compressed_audio_file = portable_audio_library_file
-                .try_clone()?
-                .take(audio_metadata.size);
+impl Options {
+    /// Returns the number of worker threads to spawn.
+    fn worker_count(&self) -> usize {
+        match self.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+            jobs => jobs,
+        }
+    }
+}
 
-            let path = directory_store.join(&audio_metadata.name);
-            let mut audio_file = File::create(&path)?;
+impl Metadata {
+    /// Returns the metadata of the audio with the given name.
+    pub fn find_audio(&self, name: &str) -> PortableAudioLibraryResult<&AudioMetadata> {
+        self.audios
+            .iter()
+            .find(|audio_metadata| audio_metadata.name == name)
+            .ok_or_else(|| PortableAudioLibraryError::AudioNotFound(name.to_string()))
+    }
 
-            compression.decompress(&mut compressed_audio_file, &mut audio_file)?;
+    /// Returns the names of the playlists containing each audio, keyed by audio name.
+    pub fn playlists_by_audio(&self) -> HashMap<&str, Vec<&str>> {
+        let mut playlists_by_audio: HashMap<&str, Vec<&str>> = HashMap::new();
 
-            audio_metadata.path = path;
-            offset += audio_metadata.size;
+        for playlist in &self.playlists {
+            for track in &playlist.tracks {
+                let playlists = playlists_by_audio.entry(track).or_default();
 
-            progress_bar.inc(1);
+                // An audio listed twice in the playlist is only reported once.
+                if playlists.last() != Some(&playlist.name.as_str()) {
+                    playlists.push(&playlist.name);
+                }
+            }
         }
 
-        progress_bar.finish_and_clear();
-        println!("{} Decompression done!", CHECK_GREEN);
+        playlists_by_audio
+    }
 
-        Ok(metadata)
+    /// Returns every playlist along with the metadata of its tracks, in order.
+    pub fn playlist_audios(
+        &self,
+    ) -> PortableAudioLibraryResult<Vec<(&Playlist, Vec<&AudioMetadata>)>> {
+        let audios: HashMap<&str, &AudioMetadata> = self
+            .audios
+            .iter()
+            .map(|audio_metadata| (audio_metadata.name.as_str(), audio_metadata))
+            .collect();
+
+        self.playlists
+            .iter()
+            .map(|playlist| {
+                let playlist_audios = playlist
+                    .tracks
+                    .iter()
+                    .map(|track| {
+                        audios
+                            .get(track.as_str())
+                            .copied()
+                            .ok_or_else(|| PortableAudioLibraryError::AudioNotFound(track.clone()))
+                    })
+                    .collect::<PortableAudioLibraryResult<_>>()?;
+
+                Ok((playlist, playlist_audios))
+            })
+            .collect()
     }
 }
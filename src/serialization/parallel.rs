@@ -0,0 +1,321 @@
+use crate::error::{PortableAudioLibraryError, PortableAudioLibraryResult};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex},
+};
+
+/// Runs the task on every item on a pool of worker threads, stopping at the first error.
+///
+/// Each worker creates its own state with `init` before taking any item.
+pub(crate) fn for_each_parallel<T, S, I, F>(
+    items: impl Iterator<Item = T> + Send,
+    worker_count: usize,
+    init: I,
+    task: F,
+) -> PortableAudioLibraryResult<()>
+where
+    T: Send,
+    I: Fn() -> PortableAudioLibraryResult<S> + Sync,
+    F: Fn(&mut S, T) -> PortableAudioLibraryResult<()> + Sync,
+{
+    let items = Mutex::new(items);
+    let error = Mutex::new(None);
+
+    std::thread::scope(|scope| {
+        for _ in 0..worker_count.max(1) {
+            scope.spawn(|| {
+                let mut state = match init() {
+                    Ok(state) => state,
+                    Err(init_error) => {
+                        error.lock().unwrap().get_or_insert(init_error);
+                        return;
+                    }
+                };
+
+                loop {
+                    if error.lock().unwrap().is_some() {
+                        break;
+                    }
+
+                    let Some(item) = items.lock().unwrap().next() else {
+                        break;
+                    };
+
+                    if let Err(task_error) = task(&mut state, item) {
+                        error.lock().unwrap().get_or_insert(task_error);
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    match error.into_inner().unwrap() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Results of [`map_parallel_ordered`] waiting for their turn to be consumed.
+struct ReorderBuffer<U> {
+    /// Index of the next result to consume.
+    next: usize,
+    results: BTreeMap<usize, U>,
+    running_workers: usize,
+    error: Option<PortableAudioLibraryError>,
+
+    /// Set when a worker panics, so the others and the consumer stop instead of waiting on it.
+    panicked: bool,
+}
+
+impl<U> ReorderBuffer<U> {
+    fn stopped(&self) -> bool {
+        self.error.is_some() || self.panicked
+    }
+}
+
+/// Signs a worker off from the [`ReorderBuffer`] when it exits, even by panicking.
+struct WorkerGuard<'a, U> {
+    buffer: &'a Mutex<ReorderBuffer<U>>,
+    changed: &'a Condvar,
+}
+
+impl<U> Drop for WorkerGuard<'_, U> {
+    fn drop(&mut self) {
+        let mut buffer = self
+            .buffer
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        buffer.running_workers -= 1;
+        buffer.panicked |= std::thread::panicking();
+        self.changed.notify_all();
+    }
+}
+
+/// Runs the task on every item on a pool of worker threads and hands the results to `consume` on
+/// the calling thread in the order of the items, stopping at the first error.
+///
+/// Workers never run more than `window` items ahead of the next result to consume, which bounds
+/// the number of results held at once.
+pub(crate) fn map_parallel_ordered<T, U, F, C>(
+    items: impl Iterator<Item = T> + Send,
+    worker_count: usize,
+    window: usize,
+    task: F,
+    mut consume: C,
+) -> PortableAudioLibraryResult<()>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> PortableAudioLibraryResult<U> + Sync,
+    C: FnMut(U) -> PortableAudioLibraryResult<()>,
+{
+    let worker_count = worker_count.max(1);
+    let window = window.max(1);
+    let items = Mutex::new(items.enumerate());
+    let buffer = Mutex::new(ReorderBuffer {
+        next: 0,
+        results: BTreeMap::new(),
+        running_workers: worker_count,
+        error: None,
+        panicked: false,
+    });
+    let changed = Condvar::new();
+
+    std::thread::scope(|scope| {
+        for _ in 0..worker_count {
+            scope.spawn(|| {
+                let _guard = WorkerGuard {
+                    buffer: &buffer,
+                    changed: &changed,
+                };
+
+                loop {
+                    let Some((index, item)) = items.lock().unwrap().next() else {
+                        break;
+                    };
+
+                    let buffer_guard = changed
+                        .wait_while(buffer.lock().unwrap(), |buffer| {
+                            !buffer.stopped() && index >= buffer.next + window
+                        })
+                        .unwrap();
+                    if buffer_guard.stopped() {
+                        break;
+                    }
+                    drop(buffer_guard);
+
+                    let result = task(item);
+
+                    let mut buffer = buffer.lock().unwrap();
+                    match result {
+                        Ok(result) => {
+                            buffer.results.insert(index, result);
+                        }
+                        Err(task_error) => {
+                            buffer.error.get_or_insert(task_error);
+                        }
+                    }
+                    changed.notify_all();
+                }
+            });
+        }
+
+        loop {
+            let mut buffer_guard = changed
+                .wait_while(buffer.lock().unwrap(), |buffer| {
+                    !buffer.stopped()
+                        && buffer.running_workers > 0
+                        && !buffer.results.contains_key(&buffer.next)
+                })
+                .unwrap();
+            if buffer_guard.stopped() {
+                break;
+            }
+            let next = buffer_guard.next;
+            let Some(result) = buffer_guard.results.remove(&next) else {
+                // Every worker is gone and every result is consumed.
+                break;
+            };
+            drop(buffer_guard);
+
+            let consumed = consume(result);
+
+            let mut buffer = buffer.lock().unwrap();
+            match consumed {
+                Ok(()) => buffer.next += 1,
+                Err(consume_error) => {
+                    buffer.error.get_or_insert(consume_error);
+                }
+            }
+            changed.notify_all();
+        }
+    });
+
+    match buffer.into_inner().unwrap().error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    fn failure(index: usize) -> PortableAudioLibraryError {
+        PortableAudioLibraryError::AudioNotFound(index.to_string())
+    }
+
+    #[test]
+    fn consumes_results_in_item_order() {
+        let mut consumed = vec![];
+
+        // Later items finish first.
+        map_parallel_ordered(
+            0..8,
+            4,
+            8,
+            |index| {
+                std::thread::sleep(Duration::from_millis(5 * (8 - index)));
+                Ok(index)
+            },
+            |index| {
+                consumed.push(index);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(consumed, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stops_on_task_error() {
+        let mut consumed = vec![];
+
+        let result = map_parallel_ordered(
+            0..100,
+            4,
+            4,
+            |index| match index {
+                3 => Err(failure(index)),
+                _ => Ok(index),
+            },
+            |index| {
+                consumed.push(index);
+                Ok(())
+            },
+        );
+
+        assert!(
+            matches!(result, Err(PortableAudioLibraryError::AudioNotFound(name)) if name == "3")
+        );
+        assert!(consumed
+            .iter()
+            .enumerate()
+            .all(|(i, &index)| i == index && index < 3));
+    }
+
+    #[test]
+    fn stops_on_consumer_error() {
+        let started = AtomicUsize::new(0);
+        let mut consumed = vec![];
+
+        let result = map_parallel_ordered(
+            0..100,
+            4,
+            4,
+            |index| {
+                started.fetch_add(1, Ordering::SeqCst);
+                Ok(index)
+            },
+            |index| {
+                consumed.push(index);
+                match index {
+                    2 => Err(failure(index)),
+                    _ => Ok(()),
+                }
+            },
+        );
+
+        assert!(
+            matches!(result, Err(PortableAudioLibraryError::AudioNotFound(name)) if name == "2")
+        );
+        assert_eq!(consumed, [0, 1, 2]);
+        // Workers can't run further than the window past the failed result.
+        assert!(started.load(Ordering::SeqCst) <= 2 + 4);
+    }
+
+    #[test]
+    fn bounds_results_held_by_window() {
+        let held = AtomicUsize::new(0);
+        let max_held = AtomicUsize::new(0);
+
+        // The first item is slow, so the others would run ahead of it without the window.
+        map_parallel_ordered(
+            0..32,
+            8,
+            3,
+            |index| {
+                let now_held = held.fetch_add(1, Ordering::SeqCst) + 1;
+                max_held.fetch_max(now_held, Ordering::SeqCst);
+                if index % 8 == 0 {
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                Ok(index)
+            },
+            |_| {
+                held.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(held.load(Ordering::SeqCst), 0);
+        assert!(max_held.load(Ordering::SeqCst) <= 3);
+    }
+}
@@ -0,0 +1,414 @@
+use super::{
+    checksum::{HashingReader, HashingWriter},
+    parallel::for_each_parallel,
+    AudioMetadata, Metadata, Options, FORMAT_VERSION, MAGIC, MAX_METADATA_SIZE, SIGNATURE_SIZE,
+    U64_SIZE,
+};
+use crate::{
+    compression::get_compression,
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    progress::Stage,
+};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+impl Metadata {
+    /// Reads the metadata from the file.
+    pub fn read_from_file(
+        path: impl Into<PathBuf>,
+        directory_store: impl Into<PathBuf>,
+    ) -> PortableAudioLibraryResult<Metadata> {
+        Self::read_from_file_with_options(path, directory_store, &Options::default())
+    }
+
+    /// Reads the metadata from the file, using the given options.
+    ///
+    /// With more than one job, every worker thread reads the audios through its own file handle.
+    pub fn read_from_file_with_options(
+        path: impl Into<PathBuf>,
+        directory_store: impl Into<PathBuf>,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<Metadata> {
+        let path = path.into();
+        let directory_store: PathBuf = directory_store.into();
+
+        let mut portable_audio_library_file = std::fs::File::open(&path)?;
+
+        if options.worker_count() == 1 {
+            return Self::read_from(portable_audio_library_file, directory_store, options);
+        }
+
+        let mut metadata = read_metadata(&mut portable_audio_library_file)?;
+
+        options
+            .progress
+            .started(Stage::Reading, metadata.audios.len() as u64);
+
+        let audio_count = metadata.audios.len();
+        for_each_parallel(
+            metadata.audios.iter_mut(),
+            options.worker_count().min(audio_count),
+            || Ok(std::fs::File::open(&path)?),
+            |portable_audio_library_file, audio_metadata| {
+                let audio_path = directory_store.join(&audio_metadata.name);
+                let audio_file = File::create(&audio_path)?;
+
+                decompress_audio(portable_audio_library_file, 0, audio_metadata, audio_file)?;
+
+                audio_metadata.path = audio_path;
+
+                options
+                    .progress
+                    .progressed(&audio_metadata.name, audio_metadata.size);
+
+                Ok(())
+            },
+        )?;
+
+        options.progress.finished(Stage::Reading);
+
+        Ok(metadata)
+    }
+
+    /// Reads the metadata from a seekable reader, decompressing every audio into the directory
+    /// store.
+    ///
+    /// The PAL data may start anywhere in the reader, as long as it is positioned at its start.
+    pub fn read_from(
+        mut reader: impl Read + Seek,
+        directory_store: impl Into<PathBuf>,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<Metadata> {
+        let directory_store: PathBuf = directory_store.into();
+
+        let start = reader.stream_position()?;
+        let mut metadata = read_metadata(&mut reader)?;
+
+        options
+            .progress
+            .started(Stage::Reading, metadata.audios.len() as u64);
+
+        for audio_metadata in &mut metadata.audios {
+            let audio_path = directory_store.join(&audio_metadata.name);
+            let audio_file = File::create(&audio_path)?;
+
+            decompress_audio(&mut reader, start, audio_metadata, audio_file)?;
+
+            audio_metadata.path = audio_path;
+
+            options
+                .progress
+                .progressed(&audio_metadata.name, audio_metadata.size);
+        }
+
+        options.progress.finished(Stage::Reading);
+
+        Ok(metadata)
+    }
+
+    /// Reads the metadata from a reader that can't seek, such as stdin.
+    ///
+    /// The audios are decompressed in the order they are stored.
+    pub fn read_from_stream(
+        mut reader: impl Read,
+        directory_store: impl Into<PathBuf>,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<Metadata> {
+        let directory_store: PathBuf = directory_store.into();
+
+        let mut metadata = read_metadata(&mut reader)?;
+
+        options
+            .progress
+            .started(Stage::Reading, metadata.audios.len() as u64);
+
+        for audio_metadata in &mut metadata.audios {
+            let audio_path = directory_store.join(&audio_metadata.name);
+            let audio_file = File::create(&audio_path)?;
+
+            decompress_stream(&mut reader, audio_metadata, audio_file)?;
+
+            audio_metadata.path = audio_path;
+
+            options
+                .progress
+                .progressed(&audio_metadata.name, audio_metadata.size);
+        }
+
+        options.progress.finished(Stage::Reading);
+
+        Ok(metadata)
+    }
+
+    /// Extracts a single audio from the file to the writer, without decompressing the other
+    /// audios.
+    pub fn extract_audio(
+        path: impl Into<PathBuf>,
+        name: &str,
+        writer: &mut impl Write,
+    ) -> PortableAudioLibraryResult<AudioMetadata> {
+        let path = path.into();
+
+        Self::extract_audio_from(std::fs::File::open(path)?, name, writer)
+    }
+
+    /// Extracts a single audio from the seekable reader to the writer, without decompressing the
+    /// other audios.
+    pub fn extract_audio_from(
+        mut reader: impl Read + Seek,
+        name: &str,
+        writer: &mut impl Write,
+    ) -> PortableAudioLibraryResult<AudioMetadata> {
+        let start = reader.stream_position()?;
+        let metadata = read_metadata(&mut reader)?;
+        let audio_metadata = metadata.find_audio(name)?;
+
+        decompress_audio(&mut reader, start, audio_metadata, writer)?;
+
+        Ok(audio_metadata.clone())
+    }
+
+    /// Extracts a single audio from a reader that can't seek to the writer.
+    ///
+    /// The compressed data of the audios stored before it is read and discarded.
+    pub fn extract_audio_from_stream(
+        mut reader: impl Read,
+        name: &str,
+        writer: &mut impl Write,
+    ) -> PortableAudioLibraryResult<AudioMetadata> {
+        let metadata = read_metadata(&mut reader)?;
+        let audio_metadata = metadata.find_audio(name)?;
+
+        for skipped_audio_metadata in &metadata.audios {
+            if skipped_audio_metadata.name == name {
+                break;
+            }
+
+            std::io::copy(
+                &mut (&mut reader).take(skipped_audio_metadata.size),
+                &mut std::io::sink(),
+            )?;
+        }
+
+        decompress_stream(&mut reader, audio_metadata, writer)?;
+
+        Ok(audio_metadata.clone())
+    }
+
+    /// Extracts the given audios from the file into the directory, without decompressing the
+    /// other audios.
+    pub fn extract_audios(
+        path: impl Into<PathBuf>,
+        names: &[impl AsRef<str>],
+        directory: impl Into<PathBuf>,
+    ) -> PortableAudioLibraryResult<Vec<AudioMetadata>> {
+        let path = path.into();
+
+        Self::extract_audios_from(std::fs::File::open(path)?, names, directory)
+    }
+
+    /// Extracts the given audios from the seekable reader into the directory, without
+    /// decompressing the other audios.
+    pub fn extract_audios_from(
+        mut reader: impl Read + Seek,
+        names: &[impl AsRef<str>],
+        directory: impl Into<PathBuf>,
+    ) -> PortableAudioLibraryResult<Vec<AudioMetadata>> {
+        let directory: PathBuf = directory.into();
+        std::fs::create_dir_all(&directory)?;
+
+        let start = reader.stream_position()?;
+        let metadata = read_metadata(&mut reader)?;
+
+        let mut audios = vec![];
+        for name in names {
+            let audio_metadata = metadata.find_audio(name.as_ref())?;
+
+            let path = directory.join(&audio_metadata.name);
+            let audio_file = File::create(&path)?;
+
+            decompress_audio(&mut reader, start, audio_metadata, audio_file)?;
+
+            let mut audio_metadata = audio_metadata.clone();
+            audio_metadata.path = path;
+            audios.push(audio_metadata);
+        }
+
+        Ok(audios)
+    }
+
+    /// Reads only the metadata from the file, without decompressing any audio.
+    ///
+    /// The offset of each audio is filled in, while the path is left empty.
+    pub fn read_header(path: impl Into<PathBuf>) -> PortableAudioLibraryResult<Metadata> {
+        let path = path.into();
+
+        Self::read_header_from(std::fs::File::open(path)?)
+    }
+
+    /// Reads only the metadata from the reader, without decompressing any audio.
+    ///
+    /// The offset of each audio is filled in, while the path is left empty.
+    pub fn read_header_from(mut reader: impl Read) -> PortableAudioLibraryResult<Metadata> {
+        read_metadata(&mut reader)
+    }
+}
+
+/// Reads the signature and the metadata, and computes the offset of every audio.
+pub(super) fn read_metadata(reader: &mut impl Read) -> PortableAudioLibraryResult<Metadata> {
+    read_signature(reader)?;
+
+    let mut metadata_size_buf = vec![0; U64_SIZE];
+    reader.read_exact(&mut metadata_size_buf)?;
+    let metadata_size = u64::from_be_bytes(metadata_size_buf.try_into().unwrap());
+
+    check_metadata_size(metadata_size)?;
+
+    // Reading through `take` only allocates as much as the reader actually holds.
+    let mut metadata_buf = vec![];
+    reader.take(metadata_size).read_to_end(&mut metadata_buf)?;
+    if metadata_buf.len() as u64 != metadata_size {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    let mut metadata: Metadata = bincode::deserialize(&metadata_buf)?;
+
+    for audio_metadata in &metadata.audios {
+        check_audio_name(&audio_metadata.name)?;
+    }
+
+    let mut offset = (SIGNATURE_SIZE + U64_SIZE) as u64 + metadata_size;
+    for audio_metadata in &mut metadata.audios {
+        audio_metadata.offset = offset;
+        offset = offset.saturating_add(audio_metadata.size);
+    }
+
+    Ok(metadata)
+}
+
+/// Rejects audio names that would resolve outside of the directory they are extracted to.
+fn check_audio_name(name: &str) -> PortableAudioLibraryResult<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(PortableAudioLibraryError::InvalidAudioName(
+            name.to_string(),
+        )),
+    }
+}
+
+/// Rejects metadata sizes that can only come from a corrupted or foreign file.
+pub(super) fn check_metadata_size(metadata_size: u64) -> PortableAudioLibraryResult<()> {
+    match metadata_size > MAX_METADATA_SIZE {
+        true => Err(PortableAudioLibraryError::MetadataTooLarge(metadata_size)),
+        false => Ok(()),
+    }
+}
+
+/// Decompresses a single audio from the reader to the writer and verifies its checksums.
+///
+/// `start` is the position of the PAL data in the reader, which the audio's offset is relative
+/// to.
+pub(super) fn decompress_audio<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    audio_metadata: &AudioMetadata,
+    writer: impl Write,
+) -> PortableAudioLibraryResult<()> {
+    reader.seek(SeekFrom::Start(start.saturating_add(audio_metadata.offset)))?;
+
+    decompress_stream(reader, audio_metadata, writer)
+}
+
+/// Decompresses a single audio from the current position of the reader to the writer and
+/// verifies its checksums.
+///
+/// Exactly the audio's compressed data is consumed from the reader, so the next audio can be
+/// read right after.
+fn decompress_stream(
+    reader: impl Read,
+    audio_metadata: &AudioMetadata,
+    writer: impl Write,
+) -> PortableAudioLibraryResult<()> {
+    let mut compressed_audio = HashingReader::new(reader.take(audio_metadata.size));
+    let mut audio = HashingWriter::new(writer);
+
+    get_compression(&audio_metadata.compression_type)
+        .decompress(&mut compressed_audio, &mut audio)?;
+
+    // Some decoders stop before the end of their input, consume the rest of it.
+    std::io::copy(&mut compressed_audio, &mut std::io::sink())?;
+
+    verify_checksum(audio_metadata, &compressed_audio, &audio)
+}
+
+/// Compares the digests of the read audio data against the stored checksums.
+fn verify_checksum<R: Read, W: Write>(
+    audio_metadata: &AudioMetadata,
+    compressed_audio: &HashingReader<R>,
+    audio: &HashingWriter<W>,
+) -> PortableAudioLibraryResult<()> {
+    let compressed_matches = audio_metadata
+        .compressed_checksum
+        .is_none_or(|checksum| checksum == compressed_audio.checksum());
+    let matches = audio_metadata
+        .checksum
+        .is_none_or(|checksum| checksum == audio.checksum());
+
+    match compressed_matches && matches {
+        true => Ok(()),
+        false => Err(PortableAudioLibraryError::ChecksumMismatch(
+            audio_metadata.name.clone(),
+        )),
+    }
+}
+
+/// Reads and validates the magic number and the format version.
+pub(super) fn read_signature(reader: &mut impl Read) -> PortableAudioLibraryResult<()> {
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+
+    if magic != MAGIC {
+        return Err(PortableAudioLibraryError::InvalidMagic);
+    }
+
+    let mut version_buf = [0; std::mem::size_of::<u16>()];
+    reader.read_exact(&mut version_buf)?;
+    let version = u16::from_be_bytes(version_buf);
+
+    if version != FORMAT_VERSION {
+        return Err(PortableAudioLibraryError::UnsupportedVersion(version));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_audio_names_leaving_the_directory() {
+        assert!(check_audio_name("song.mp3").is_ok());
+        assert!(check_audio_name("..hidden.mp3").is_ok());
+
+        for name in [
+            "",
+            ".",
+            "..",
+            "../escaped.mp3",
+            "sub/song.mp3",
+            "/tmp/song.mp3",
+        ] {
+            assert!(
+                matches!(
+                    check_audio_name(name),
+                    Err(PortableAudioLibraryError::InvalidAudioName(_))
+                ),
+                "{name:?} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::{
+    reader::{decompress_audio, read_metadata},
+    Metadata, Options,
+};
+use crate::{
+    error::{PortableAudioLibraryError, PortableAudioLibraryResult},
+    progress::Stage,
+};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+/// Result of verifying a PAL file.
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+    /// Size of the file according to the metadata, `None` if the audio sizes overflow.
+    pub expected_size: Option<u64>,
+
+    /// Actual size of the file.
+    pub actual_size: u64,
+
+    /// Names of the audios that failed verification, along with the reason.
+    pub failures: Vec<(String, PortableAudioLibraryError)>,
+}
+
+impl VerificationReport {
+    /// Returns `true` if the file size matches and every audio passed verification.
+    pub fn is_ok(&self) -> bool {
+        self.expected_size == Some(self.actual_size) && self.failures.is_empty()
+    }
+}
+
+impl Metadata {
+    /// Verifies the integrity of every audio in the file.
+    ///
+    /// Each audio is decompressed and checked against its stored checksums. Failing audios are
+    /// collected into the report instead of stopping at the first one.
+    pub fn verify_file(path: impl Into<PathBuf>) -> PortableAudioLibraryResult<VerificationReport> {
+        Self::verify_file_with_options(path, &Options::default())
+    }
+
+    /// Verifies the integrity of every audio in the file, using the given options.
+    pub fn verify_file_with_options(
+        path: impl Into<PathBuf>,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<VerificationReport> {
+        let path = path.into();
+
+        Self::verify(std::fs::File::open(path)?, options)
+    }
+
+    /// Verifies the integrity of every audio in the seekable reader.
+    ///
+    /// The PAL data is expected to span from the current position to the end of the reader.
+    pub fn verify(
+        mut reader: impl Read + Seek,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<VerificationReport> {
+        let start = reader.stream_position()?;
+        let metadata = read_metadata(&mut reader)?;
+
+        let data_offset = reader.stream_position()? - start;
+
+        let mut report = VerificationReport {
+            expected_size: metadata
+                .audios
+                .iter()
+                .try_fold(data_offset, |size, audio_metadata| {
+                    size.checked_add(audio_metadata.size)
+                }),
+            actual_size: reader.seek(SeekFrom::End(0))? - start,
+            failures: vec![],
+        };
+
+        options
+            .progress
+            .started(Stage::Verifying, metadata.audios.len() as u64);
+
+        for audio_metadata in &metadata.audios {
+            if let Err(error) =
+                decompress_audio(&mut reader, start, audio_metadata, std::io::sink())
+            {
+                report.failures.push((audio_metadata.name.clone(), error));
+            }
+
+            options
+                .progress
+                .progressed(&audio_metadata.name, audio_metadata.size);
+        }
+
+        options.progress.finished(Stage::Verifying);
+
+        Ok(report)
+    }
+}
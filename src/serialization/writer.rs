@@ -0,0 +1,236 @@
+use super::{
+    checksum::{HashingReader, HashingWriter},
+    parallel::map_parallel_ordered,
+    AudioMetadata, Checksum, Metadata, Options, CHUNK_SIZE, FORMAT_VERSION, MAGIC, SIGNATURE_SIZE,
+};
+use crate::{
+    compression::{auto, get_compression, CompressionType},
+    error::PortableAudioLibraryResult,
+    progress::Stage,
+};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+use tempfile::tempfile;
+
+/// Checksum written in place of the real one until the audio is compressed.
+const PLACEHOLDER_CHECKSUM: Checksum = [0; 32];
+
+impl Metadata {
+    /// Writes the metadata to the file.
+    pub fn write_to_file(&mut self, path: impl Into<PathBuf>) -> PortableAudioLibraryResult<()> {
+        self.write_to_file_with_options(path, &Options::default())
+    }
+
+    /// Writes the metadata to the file, using the given options.
+    pub fn write_to_file_with_options(
+        &mut self,
+        path: impl Into<PathBuf>,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<()> {
+        let path = path.into();
+
+        let mut portable_audio_library_file = std::fs::File::create(path)?;
+        self.write_to_seekable(&mut portable_audio_library_file, options)
+    }
+
+    /// Writes the metadata to a seekable writer, compressing the audios straight into it.
+    ///
+    /// The metadata is written first with placeholder sizes and checksums, then patched once
+    /// every audio is written. With a single job no temporary file is used, otherwise each
+    /// worker compresses one audio at a time into a temporary file, and the files are appended
+    /// in order as soon as they are ready.
+    pub fn write_to_seekable<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<()> {
+        self.prepare_audios()?;
+
+        let metadata_offset = writer.stream_position()? + SIGNATURE_SIZE as u64;
+        write_signature(writer)?;
+        let metadata_size = write_metadata(writer, self, 0)?;
+
+        self.write_audios(writer, options)?;
+
+        let end_offset = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(metadata_offset))?;
+        write_metadata(writer, self, metadata_size)?;
+        writer.seek(SeekFrom::Start(end_offset))?;
+
+        options.progress.finished(Stage::Writing);
+
+        Ok(())
+    }
+
+    /// Writes the metadata to a writer that can't seek, such as stdout.
+    ///
+    /// The audios are compressed into a single temporary file first, since the metadata needs
+    /// their sizes and checksums before it can be written.
+    pub fn write_to(
+        &mut self,
+        mut writer: impl Write,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<()> {
+        self.prepare_audios()?;
+
+        let mut compressed_audio_file = tempfile()?;
+        self.write_audios(&mut compressed_audio_file, options)?;
+
+        write_signature(&mut writer)?;
+        write_metadata(&mut writer, self, 0)?;
+        append_file(compressed_audio_file, &mut writer)?;
+
+        options.progress.finished(Stage::Writing);
+
+        Ok(())
+    }
+
+    /// Applies the compression policy to every audio and fills in placeholder sizes and
+    /// checksums.
+    ///
+    /// [`CompressionType::Auto`] is left in place and resolved when the audio is compressed.
+    fn prepare_audios(&mut self) -> PortableAudioLibraryResult<()> {
+        for audio in &mut self.audios {
+            audio.compression_type = self.compression_policy.select(
+                &self.compression_type,
+                &audio.compression_type,
+                &audio.name,
+            );
+
+            // Sizes and checksums are fixed-size in bincode, so the placeholders reserve exactly
+            // the space needed by the real values. `Auto` is as large as any compression type it
+            // resolves to.
+            audio.size = 0;
+            audio.checksum = Some(PLACEHOLDER_CHECKSUM);
+            audio.compressed_checksum = Some(PLACEHOLDER_CHECKSUM);
+        }
+
+        Ok(())
+    }
+
+    /// Compresses every audio to the writer in order.
+    fn write_audios(
+        &mut self,
+        writer: &mut impl Write,
+        options: &Options,
+    ) -> PortableAudioLibraryResult<()> {
+        options
+            .progress
+            .started(Stage::Writing, self.audios.len() as u64);
+
+        match options.worker_count() {
+            1 => {
+                for audio in &mut self.audios {
+                    compress_audio(audio, &mut *writer)?;
+
+                    options.progress.progressed(&audio.name, audio.size);
+                }
+            }
+            worker_count => {
+                // Lets fast audios run ahead of a slow one, while bounding the number of
+                // temporary files.
+                map_parallel_ordered(
+                    self.audios.iter_mut(),
+                    worker_count,
+                    2 * worker_count,
+                    |audio| {
+                        let mut compressed_audio_file = tempfile()?;
+                        compress_audio(audio, &mut compressed_audio_file)?;
+
+                        Ok((audio, compressed_audio_file))
+                    },
+                    |(audio, compressed_audio_file)| {
+                        append_file(compressed_audio_file, writer)?;
+
+                        options.progress.progressed(&audio.name, audio.size);
+
+                        Ok(())
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Trial compresses the audio to pick its compression type if it is [`CompressionType::Auto`].
+fn resolve_compression_type(audio: &mut AudioMetadata) -> PortableAudioLibraryResult<()> {
+    if let CompressionType::Auto(threshold) = audio.compression_type {
+        let mut audio_file = std::fs::File::open(&audio.path)?;
+        audio.compression_type = auto::select_compression_type(&mut audio_file, threshold)?;
+    }
+
+    Ok(())
+}
+
+/// Compresses a single audio to the writer, filling in its size and checksums.
+fn compress_audio(audio: &mut AudioMetadata, writer: impl Write) -> PortableAudioLibraryResult<()> {
+    resolve_compression_type(audio)?;
+    let compression = get_compression(&audio.compression_type);
+
+    let mut audio_file = HashingReader::new(std::fs::File::open(&audio.path)?);
+    let mut compressed_audio = HashingWriter::new(writer);
+
+    compression.compress(&mut audio_file, &mut compressed_audio)?;
+
+    audio.checksum = Some(audio_file.checksum());
+    audio.compressed_checksum = Some(compressed_audio.checksum());
+    audio.size = compressed_audio.size();
+
+    Ok(())
+}
+
+/// Copies the whole file to the writer.
+fn append_file(mut file: File, writer: &mut impl Write) -> PortableAudioLibraryResult<()> {
+    file.seek(SeekFrom::Start(0))?;
+    loop {
+        let mut chunk = vec![0; CHUNK_SIZE]; // 16 MB chunk
+        let bytes_read = file.read(&mut chunk)?;
+
+        if bytes_read == 0 {
+            break;
+        }
+
+        writer.write_all(&chunk[..bytes_read])?;
+    }
+
+    Ok(())
+}
+
+/// Writes the size of the serialized metadata followed by the metadata itself, returning the
+/// size.
+///
+/// The metadata is padded with zeros up to `reserved_size`, bincode ignores the trailing bytes
+/// when reading. It must not outgrow a nonzero `reserved_size`, since it would overwrite what
+/// follows it.
+fn write_metadata(
+    writer: &mut impl Write,
+    metadata: &Metadata,
+    reserved_size: u64,
+) -> PortableAudioLibraryResult<u64> {
+    let mut serialized_portable_audio_library = bincode::serialize(metadata)?;
+    let serialized_size = serialized_portable_audio_library.len() as u64;
+
+    if reserved_size != 0 && serialized_size > reserved_size {
+        return Err(std::io::Error::other("metadata outgrew its reserved space").into());
+    }
+    serialized_portable_audio_library.resize(serialized_size.max(reserved_size) as usize, 0);
+
+    let metadata_size = serialized_portable_audio_library.len() as u64;
+    writer.write_all(&metadata_size.to_be_bytes())?;
+    writer.write_all(&serialized_portable_audio_library)?;
+
+    Ok(metadata_size)
+}
+
+/// Writes the magic number and the format version.
+fn write_signature(writer: &mut impl Write) -> PortableAudioLibraryResult<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_be_bytes())?;
+
+    Ok(())
+}